@@ -1,20 +1,310 @@
+use bevy::app::AppExit;
 use bevy::diagnostic::{FrameTimeDiagnosticsPlugin, LogDiagnosticsPlugin};
 use bevy::prelude::*;
-use bevy::window::PresentMode;
-use rand::Rng;
+use bevy::render::camera::ScalingMode;
+use bevy::render::mesh::Indices;
+use bevy::render::render_resource::PrimitiveTopology;
+use bevy::window::{PresentMode, WindowMode};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
 
 const DOMAIN: f32 = 30.0;
 const PARTICLE_SIZE: f32 = 0.7;
 const TARGET_SIZE: f32 = 1.5;
 const LERP_SPEED: f32 = 4.5; // Kecepatan smooth movement (1.0-10.0)
+const GEN_INTERVAL: f64 = 0.3; // Jeda antar generasi saat auto (detik)
+const MAX_VISUAL_JUMP: f32 = 6.0; // Lompatan visual maksimum per generasi
+const CONVERGE_TOLERANCE: f32 = 0.7; // Jarak (menurut metric aktif) dianggap sampai
+const MAX_POPULATION: usize = 500; // Batas atas populasi lewat keyboard
+const POP_STEP_FAST: usize = 50; // Langkah [U][J] saat Shift ditahan
+const V_MAX_STEP: f32 = 1.0; // Langkah [Y][H] untuk v_max
+const RING_NEIGHBORS: usize = 1; // Tetangga indeks di tiap sisi untuk topologi Ring
+const PARTICLE_HUES: usize = 16; // Jumlah material warna yang dibagi antar partikel
+const DRIFT_RADIUS: f32 = 8.0; // Jari-jari lintasan target saat drift Circle
+const DRIFT_ANGLE_STEP: f32 = 0.15; // Radian per generasi untuk drift Circle
+const DRIFT_WALK_STEP: f32 = 1.5; // Langkah maksimum per generasi untuk drift RandomWalk
+const EARLY_CONVERGE_FRACTION: f32 = 0.3; // Konvergen sebelum fraksi ini dari generasi = terlalu cepat
+const STAGNATION_GAIN: f32 = 0.05; // Perbaikan gbest < 5% di sepertiga akhir = stagnan
+const OPTIMUM_EPSILON: f32 = 0.05; // Fungsi benchmark: konvergen kalau gbest sedekat ini ke minimum global
+const LANDSCAPE_RESOLUTION: usize = 120; // Sel per sisi heightfield
+const LANDSCAPE_DEPTH: f32 = 8.0; // Jarak vertikal puncak -> lembah heightfield (di bawah partikel)
+
+// Metric jarak ke target yang dipakai sebagai fitness
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Metric {
+    L1,
+    L2,
+    Chebyshev,
+}
+
+impl Metric {
+    fn distance(&self, a: Vec2, b: Vec2) -> f32 {
+        let d = (a - b).abs();
+        match self {
+            Metric::L1 => d.x + d.y,
+            Metric::L2 => d.length(),
+            Metric::Chebyshev => d.x.max(d.y),
+        }
+    }
+
+    fn next(&self) -> Metric {
+        match self {
+            Metric::L1 => Metric::L2,
+            Metric::L2 => Metric::Chebyshev,
+            Metric::Chebyshev => Metric::L1,
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Metric::L1 => "L1",
+            Metric::L2 => "L2",
+            Metric::Chebyshev => "Chebyshev",
+        }
+    }
+}
+
+// Fungsi yang diminimalkan ([F]). Target = jarak ke titik klik; sisanya fungsi
+// benchmark klasik yang domain standarnya direntangkan ke ±DOMAIN. Semua punya
+// minimum global 0.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Objective {
+    Target { goal: Vec2, metric: Metric },
+    Sphere,
+    Rastrigin,
+    Ackley,
+    Rosenbrock,
+}
+
+impl Objective {
+    fn eval(&self, pos: Vec2) -> f32 {
+        use std::f32::consts::{E, TAU};
+        let p = pos * self.native_range() / DOMAIN;
+        match self {
+            Objective::Target { goal, metric } => metric.distance(pos, *goal),
+            Objective::Sphere => p.length_squared(),
+            Objective::Rastrigin => {
+                20.0 + p.x * p.x - 10.0 * (TAU * p.x).cos() + p.y * p.y - 10.0 * (TAU * p.y).cos()
+            }
+            Objective::Ackley => {
+                -20.0 * (-0.2 * (0.5 * p.length_squared()).sqrt()).exp()
+                    - (0.5 * ((TAU * p.x).cos() + (TAU * p.y).cos())).exp()
+                    + E
+                    + 20.0
+            }
+            Objective::Rosenbrock => (1.0 - p.x).powi(2) + 100.0 * (p.y - p.x * p.x).powi(2),
+        }
+    }
+
+    // Setengah lebar domain standar fungsi (yang dipetakan ke ±DOMAIN)
+    fn native_range(&self) -> f32 {
+        match self {
+            Objective::Target { .. } => DOMAIN,
+            Objective::Sphere | Objective::Rastrigin => 5.12,
+            Objective::Ackley => 5.0,
+            Objective::Rosenbrock => 2.048,
+        }
+    }
+
+    // Posisi minimum global di ruang algoritma
+    fn optimum(&self) -> Vec2 {
+        match self {
+            Objective::Target { goal, .. } => *goal,
+            Objective::Rosenbrock => Vec2::ONE * DOMAIN / self.native_range(),
+            _ => Vec2::ZERO,
+        }
+    }
+
+    // Selisih gbest dari minimum (0) yang dianggap konvergen
+    fn tolerance(&self) -> f32 {
+        match self {
+            Objective::Target { .. } => CONVERGE_TOLERANCE,
+            _ => OPTIMUM_EPSILON,
+        }
+    }
+
+    // Benchmark berikutnya; None = kembali ke mode klik target
+    fn next_benchmark(&self) -> Option<Objective> {
+        match self {
+            Objective::Target { .. } => Some(Objective::Sphere),
+            Objective::Sphere => Some(Objective::Rastrigin),
+            Objective::Rastrigin => Some(Objective::Ackley),
+            Objective::Ackley => Some(Objective::Rosenbrock),
+            Objective::Rosenbrock => None,
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Objective::Target { metric, .. } => metric.name(),
+            Objective::Sphere => "Sphere",
+            Objective::Rastrigin => "Rastrigin",
+            Objective::Ackley => "Ackley",
+            Objective::Rosenbrock => "Rosenbrock",
+        }
+    }
+}
+
+// Sumber tarikan social ([/]): gbest seluruh swarm, atau lbest = pbest terbaik
+// di antara k tetangga indeks kiri-kanan (melingkar) plus partikel itu sendiri.
+// Informasi menyebar pelan di ring, jadi swarm lebih lama menjelajah.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Topology {
+    Global,
+    Ring { k: usize },
+}
+
+impl Topology {
+    fn next(&self) -> Topology {
+        match self {
+            Topology::Global => Topology::Ring { k: RING_NEIGHBORS },
+            Topology::Ring { .. } => Topology::Global,
+        }
+    }
+
+    fn name(&self) -> String {
+        match self {
+            Topology::Global => "gbest".to_string(),
+            Topology::Ring { k } => format!("ring k={}", k),
+        }
+    }
+
+    // Posisi yang menarik partikel ke-i lewat term social
+    fn social_best(&self, particles: &[Particle], i: usize, gbest: Vec2) -> Vec2 {
+        let Topology::Ring { k } = *self else {
+            return gbest;
+        };
+        let n = particles.len() as isize;
+        (-(k as isize)..=k as isize)
+            .map(|offset| &particles[(i as isize + offset).rem_euclid(n) as usize])
+            .min_by(|a, b| a.pbest_val.total_cmp(&b.pbest_val))
+            .map_or(gbest, |best| best.pbest_pos)
+    }
+}
+
+// Sebaran posisi awal partikel ([Z])
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum InitMode {
+    Uniform,  // Merata di seluruh domain
+    Gaussian, // Normal di sekitar pusat, sigma = DOMAIN / 4
+    Corner,   // Berkumpul di satu pojok (kuadran kecil kiri-bawah)
+}
+
+impl InitMode {
+    fn next(&self) -> InitMode {
+        match self {
+            InitMode::Uniform => InitMode::Gaussian,
+            InitMode::Gaussian => InitMode::Corner,
+            InitMode::Corner => InitMode::Uniform,
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            InitMode::Uniform => "uniform",
+            InitMode::Gaussian => "gaussian",
+            InitMode::Corner => "corner",
+        }
+    }
+
+    fn sample(&self, rng: &mut impl Rng) -> Vec2 {
+        let pos = match self {
+            InitMode::Uniform => Vec2::new(
+                rng.gen_range(-DOMAIN..DOMAIN),
+                rng.gen_range(-DOMAIN..DOMAIN),
+            ),
+            InitMode::Gaussian => {
+                // Box-Muller, dua sampel normal dari dua sampel uniform
+                let u1: f32 = rng.gen_range(f32::EPSILON..1.0);
+                let u2: f32 = rng.gen_range(0.0..1.0);
+                let radius = (-2.0 * u1.ln()).sqrt() * DOMAIN / 4.0;
+                let angle = std::f32::consts::TAU * u2;
+                Vec2::new(radius * angle.cos(), radius * angle.sin())
+            }
+            InitMode::Corner => Vec2::new(
+                rng.gen_range(-DOMAIN..-DOMAIN * 0.7),
+                rng.gen_range(-DOMAIN..-DOMAIN * 0.7),
+            ),
+        };
+        pos.clamp(Vec2::splat(-DOMAIN), Vec2::splat(DOMAIN))
+    }
+}
+
+// Target bergerak tiap generasi ([X]) untuk demo optimasi dinamis
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Drift {
+    Off,
+    Circle,     // Memutari titik klik
+    RandomWalk, // Langkah acak, tetap di dalam domain
+}
+
+impl Drift {
+    fn next(&self) -> Drift {
+        match self {
+            Drift::Off => Drift::Circle,
+            Drift::Circle => Drift::RandomWalk,
+            Drift::RandomWalk => Drift::Off,
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Drift::Off => "static",
+            Drift::Circle => "drift circle",
+            Drift::RandomWalk => "drift walk",
+        }
+    }
+
+    // Posisi target untuk generasi `generation`; origin = titik yang diklik
+    fn advance(&self, current: Vec2, origin: Vec2, generation: usize, rng: &mut impl Rng) -> Vec2 {
+        match self {
+            Drift::Off => current,
+            Drift::Circle => {
+                let angle = generation as f32 * DRIFT_ANGLE_STEP;
+                origin + DRIFT_RADIUS * Vec2::new(angle.cos(), angle.sin())
+            }
+            Drift::RandomWalk => {
+                let step = Vec2::new(
+                    rng.gen_range(-DRIFT_WALK_STEP..DRIFT_WALK_STEP),
+                    rng.gen_range(-DRIFT_WALK_STEP..DRIFT_WALK_STEP),
+                );
+                (current + step).clamp(Vec2::splat(-DOMAIN), Vec2::splat(DOMAIN))
+            }
+        }
+    }
+}
 
 #[derive(Clone, Copy)]
 struct PsoParams {
     population: usize,
     generations: usize,
     w: f32,
+    // Inertia turun linear w_start -> w_end sepanjang generasi ([,]); kalau
+    // mati, w konstan dari [I][K]
+    anneal_w: bool,
+    w_start: f32,
+    w_end: f32,
     c1: f32,
     c2: f32,
+    v_max: f32, // Batas panjang velocity per generasi
+    clamp_jumps: bool,
+    metric: Metric,
+    topology: Topology,
+    drift: Drift,
+    init: InitMode,
+    log_view: bool, // Tampilan signed-log ([R]); optimisasi tetap di ruang linear
+    sprites: bool,  // Partikel sebagai quad billboard unlit ([T]), lebih murah dari sphere
+}
+
+impl PsoParams {
+    // Inertia untuk generasi ke-`generation`; generasi refinement memakai w_end
+    fn effective_w(&self, generation: usize) -> f32 {
+        if !self.anneal_w {
+            return self.w;
+        }
+        let progress = (generation as f32 / self.generations.max(1) as f32).min(1.0);
+        self.w_start + (self.w_end - self.w_start) * progress
+    }
 }
 
 impl Default for PsoParams {
@@ -23,19 +313,64 @@ impl Default for PsoParams {
             population: 10,
             generations: 15,
             w: 0.6,
+            anneal_w: false,
+            w_start: 0.9,
+            w_end: 0.4,
             c1: 1.8,
             c2: 2.1,
+            v_max: 0.2 * DOMAIN,
+            clamp_jumps: false,
+            metric: Metric::L2,
+            topology: Topology::Global,
+            drift: Drift::Off,
+            init: InitMode::Uniform,
+            log_view: false,
+            sprites: false,
         }
     }
 }
 
+// Signed-log per sumbu, diskalakan supaya ±DOMAIN tetap di ±DOMAIN:
+// detail dekat 0 melebar, pinggiran domain memadat.
+fn signed_log(v: f32) -> f32 {
+    v.signum() * DOMAIN * v.abs().ln_1p() / DOMAIN.ln_1p()
+}
+
+// Kebalikan signed_log
+fn signed_exp(v: f32) -> f32 {
+    v.signum() * (v.abs() * DOMAIN.ln_1p() / DOMAIN).exp_m1()
+}
+
+// Posisi algoritma -> posisi di dunia (yang dirender)
+fn to_view(pos: Vec2, log_view: bool) -> Vec2 {
+    if log_view {
+        Vec2::new(signed_log(pos.x), signed_log(pos.y))
+    } else {
+        pos
+    }
+}
+
+// Posisi di dunia (mis. hasil klik) -> posisi algoritma
+fn from_view(pos: Vec2, log_view: bool) -> Vec2 {
+    if log_view {
+        Vec2::new(signed_exp(pos.x), signed_exp(pos.y))
+    } else {
+        pos
+    }
+}
+
 #[derive(Clone, Copy, Debug)]
 struct Particle {
     position: Vec2,        // Current visual position (smooth)
-    target_position: Vec2, // Target position after PSO calculation
+    target_position: Vec2, // Rendered target (boleh tertinggal dari pos saat clamp aktif)
+    pos: Vec2,             // Posisi sebenarnya dalam algoritma
     velocity: Vec2,
     pbest_pos: Vec2,
     pbest_val: f32,
+    // Komponen update velocity terakhir: c1*r1*(pbest-pos) dan c2*r2*(gbest-pos),
+    // dengan lbest menggantikan gbest di topologi Ring
+    cognitive: Vec2,
+    social: Vec2,
 }
 
 #[derive(Resource)]
@@ -46,8 +381,19 @@ struct PsoState {
     gbest_val: f32,
     current_gen: usize,
     paused: bool,
+    // Dengan drift aktif, converged = gbest sedang dalam toleransi target saat ini
     converged: bool,
-    target: Option<Vec2>,
+    target: Option<Vec2>, // Titik klik, atau optimum objective benchmark
+    target_origin: Vec2,  // Titik klik, pusat lintasan drift Circle
+    objective: Objective,
+    history: Vec<f32>, // gbest tiap generasi run ini, untuk tuning_hint
+}
+
+impl PsoState {
+    // Drift hanya berlaku untuk target klik; optimum benchmark tetap di tempat
+    fn drifting(&self) -> bool {
+        self.params.drift != Drift::Off && matches!(self.objective, Objective::Target { .. })
+    }
 }
 
 #[derive(Component)]
@@ -60,11 +406,69 @@ struct GenText;
 struct ControlsText;
 #[derive(Component)]
 struct FpsText;
+#[derive(Component)]
+struct TargetPromptText;
+// Label generasi + gbest yang mengikuti swarm di layar
+#[derive(Component)]
+struct SwarmLabel;
+#[derive(Component)]
+struct ForceText;
+// Heightfield warna dari objective benchmark aktif
+#[derive(Component)]
+struct Landscape;
+
+// Partikel yang dipilih dengan klik kanan; tarikan pbest/gbest-nya digambar
+#[derive(Resource, Default)]
+struct SelectedParticle(Option<usize>);
 
 #[derive(Resource, Default)]
 struct ClickMarker(pub Option<Vec2>);
 
+// Garis tipis partikel -> gbest ([B]); `visual` mengejar gbest_pos dengan lerp
+#[derive(Resource, Default)]
+struct GbestLines {
+    show: bool,
+    visual: Option<Vec2>,
+}
+
+// Mesh & material partikel dibuat sekali di setup lalu di-clone per partikel,
+// jadi spawn ratusan partikel tidak menambah asset baru.
+#[derive(Resource)]
+struct ParticleAssets {
+    mesh: Handle<Mesh>,
+    materials: Vec<Handle<StandardMaterial>>,
+    sprite_mesh: Handle<Mesh>,
+    sprite_materials: Vec<Handle<StandardMaterial>>,
+}
+
+impl ParticleAssets {
+    // Mesh & material untuk partikel ke-i dari `count`; gradasi warna tetap
+    // menyebar ke seluruh palet berapa pun populasinya
+    fn handles(
+        &self,
+        i: usize,
+        count: usize,
+        sprites: bool,
+    ) -> (&Handle<Mesh>, &Handle<StandardMaterial>) {
+        let bucket = i * self.materials.len() / count.max(1);
+        if sprites {
+            (&self.sprite_mesh, &self.sprite_materials[bucket])
+        } else {
+            (&self.mesh, &self.materials[bucket])
+        }
+    }
+}
+
 fn main() {
+    // Mode benchmark headless: --compare <budget evaluasi> [--seed N]
+    if let Some(budget) = arg_value("--compare").and_then(|v| v.parse().ok()) {
+        let seed = arg_value("--seed")
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(0);
+        run_comparison(budget, seed);
+        return;
+    }
+
     App::new()
         .insert_resource(ClearColor(Color::rgb(0.025, 0.028, 0.058)))
         .insert_resource(PsoState {
@@ -76,14 +480,21 @@ fn main() {
             paused: true,
             converged: false,
             target: None,
+            target_origin: Vec2::ZERO,
+            objective: Objective::Target {
+                goal: Vec2::ZERO,
+                metric: Metric::L2,
+            },
+            history: Vec::new(),
         })
         .insert_resource(ClickMarker(None))
+        .init_resource::<GbestLines>()
+        .init_resource::<SelectedParticle>()
         .add_plugins((
             DefaultPlugins.set(WindowPlugin {
                 primary_window: Some(Window {
-                    title: "PSO Visualization - Smooth Animation".to_string(),
                     present_mode: PresentMode::AutoNoVsync,
-                    ..default()
+                    ..window_from_args("PSO Visualization - Smooth Animation")
                 }),
                 ..default()
             }),
@@ -91,6 +502,7 @@ fn main() {
             LogDiagnosticsPlugin::default(),
         ))
         .add_systems(Startup, setup)
+        .add_systems(Last, print_summary_on_exit)
         .add_systems(
             Update,
             (
@@ -98,15 +510,115 @@ fn main() {
                 mouse_set_target,
                 update_generation_text,
                 update_fps_text,
+                update_target_prompt,
                 update_ui_sliders,
                 update_particles_visual,
                 pso_tick,
+                sync_target_marker,
+                update_swarm_label,
+                draw_gbest_lines,
+                select_particle,
+                draw_particle_forces,
+                toggle_fullscreen,
+                time_scale_system,
+                toggle_projection,
+                update_landscape,
             ),
         )
         .run();
 }
 
-fn setup(mut commands: Commands) {
+fn arg_value(name: &str) -> Option<String> {
+    let args: Vec<String> = std::env::args().collect();
+    args.iter()
+        .position(|arg| arg == name)
+        .and_then(|i| args.get(i + 1).cloned())
+}
+
+// Window dari CLI: --width, --height (default 1280x720), --fullscreen
+fn window_from_args(title: &str) -> Window {
+    let width = arg_value("--width")
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(1280.0);
+    let height = arg_value("--height")
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(720.0);
+    let fullscreen = std::env::args().any(|arg| arg == "--fullscreen");
+    Window {
+        title: title.to_string(),
+        resolution: (width, height).into(),
+        mode: if fullscreen {
+            WindowMode::BorderlessFullscreen
+        } else {
+            WindowMode::Windowed
+        },
+        ..default()
+    }
+}
+
+fn toggle_fullscreen(keyboard: Res<Input<KeyCode>>, mut windows: Query<&mut Window>) {
+    if !keyboard.just_pressed(KeyCode::F11) {
+        return;
+    }
+    for mut window in windows.iter_mut() {
+        window.mode = match window.mode {
+            WindowMode::Windowed => WindowMode::BorderlessFullscreen,
+            _ => WindowMode::Windowed,
+        };
+    }
+}
+
+// [F1] 0.25x, [F2] 1x, [F3] 4x: skala waktu global untuk semua sistem yang memakai Time
+fn time_scale_system(keyboard: Res<Input<KeyCode>>, mut time: ResMut<Time>) {
+    let scale = if keyboard.just_pressed(KeyCode::F1) {
+        0.25
+    } else if keyboard.just_pressed(KeyCode::F2) {
+        1.0
+    } else if keyboard.just_pressed(KeyCode::F3) {
+        4.0
+    } else {
+        return;
+    };
+    time.set_relative_speed(scale);
+    println!("⏱️ Time scale: {}x", scale);
+}
+
+fn setup(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    let hue_color =
+        |i: usize| Color::hsl(200.0 + i as f32 / PARTICLE_HUES as f32 * 120.0, 0.8, 0.65);
+    commands.insert_resource(ParticleAssets {
+        mesh: meshes.add(Mesh::from(shape::UVSphere {
+            radius: PARTICLE_SIZE,
+            sectors: 14,
+            stacks: 14,
+        })),
+        materials: (0..PARTICLE_HUES)
+            .map(|i| {
+                materials.add(StandardMaterial {
+                    base_color: hue_color(i),
+                    emissive: Color::rgb(0.1, 0.2, 0.5),
+                    ..default()
+                })
+            })
+            .collect(),
+        sprite_mesh: meshes.add(Mesh::from(shape::Quad::new(Vec2::splat(
+            PARTICLE_SIZE * 2.0,
+        )))),
+        sprite_materials: (0..PARTICLE_HUES)
+            .map(|i| {
+                materials.add(StandardMaterial {
+                    base_color: hue_color(i),
+                    unlit: true,
+                    ..default()
+                })
+            })
+            .collect(),
+    });
+
     commands.spawn(Camera3dBundle {
         transform: Transform::from_xyz(0.0, 38.0, 38.0).looking_at(Vec3::ZERO, Vec3::Y),
         ..default()
@@ -123,6 +635,26 @@ fn setup(mut commands: Commands) {
         ..default()
     });
 
+    // Marker target / optimum, disembunyikan sampai ada target
+    let mark_color = Color::rgb(1.0, 0.15, 0.15);
+    commands.spawn((
+        PbrBundle {
+            mesh: meshes.add(Mesh::from(shape::UVSphere {
+                radius: TARGET_SIZE,
+                sectors: 20,
+                stacks: 20,
+            })),
+            material: materials.add(StandardMaterial {
+                base_color: mark_color,
+                emissive: mark_color,
+                ..default()
+            }),
+            visibility: Visibility::Hidden,
+            ..default()
+        },
+        TargetMarker,
+    ));
+
     // Title
     commands.spawn((
         TextBundle::from_section(
@@ -147,11 +679,26 @@ fn setup(mut commands: Commands) {
         TextBundle::from_section(
             "Controls:
 Click = Set Target
-[G] step/auto   [P] pause
+[G] step/auto (after convergence: refine)   [P] pause
 [+][-] generations
-[U][J] pop ±   [I][K] w ±
+[U][J] pop ± (Shift: ±50, max 500)
+[I][K] w ±   [,] constant / annealed w (0.9 -> 0.4)
 [O][L] c1 ±   [;][P] c2 ±
+[Y][H] v_max ±
 [N] new random
+[C] clamp visual jumps
+[M] metric L1/L2/Cheb
+[/] topology gbest / ring (lbest)
+[F] objective: click target / Sphere / Rastrigin / Ackley / Rosenbrock
+[X] target drift off/circle/walk
+[Z] init uniform/gauss/corner (next swarm)
+[B] lines to gbest
+[R] linear / signed-log view
+[T] sphere / sprite particles
+Right click = show forces on a particle
+[V] 2D / 3D view
+[F1][F2][F3] time 0.25x / 1x / 4x
+[F11] fullscreen
 [ESC] exit",
             TextStyle {
                 font_size: 14.0,
@@ -168,6 +715,23 @@ Click = Set Target
         ControlsText,
     ));
 
+    // Label yang menempel di atas swarm (posisi diatur update_swarm_label)
+    commands.spawn((
+        TextBundle::from_section(
+            "",
+            TextStyle {
+                font_size: 16.0,
+                color: Color::WHITE,
+                ..default()
+            },
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            ..default()
+        }),
+        SwarmLabel,
+    ));
+
     // Gen info
     commands.spawn((
         TextBundle::from_section(
@@ -187,6 +751,65 @@ Click = Set Target
         GenText,
     ));
 
+    // Keterangan panah gaya partikel terpilih
+    commands.spawn((
+        TextBundle::from_section(
+            "",
+            TextStyle {
+                font_size: 15.0,
+                color: Color::rgb(0.85, 0.9, 1.0),
+                ..default()
+            },
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            bottom: Val::Px(46.0),
+            left: Val::Px(18.0),
+            ..default()
+        }),
+        ForceText,
+    ));
+
+    // Legenda warna
+    spawn_legend(
+        &mut commands,
+        "Legend",
+        &[
+            (Color::hsl(200.0, 0.8, 0.65), "Particle (first index)"),
+            (Color::hsl(320.0, 0.8, 0.65), "Particle (last index)"),
+            (Color::rgb(1.0, 0.15, 0.15), "Target / known optimum"),
+            (Color::rgb(1.0, 0.85, 0.3), "Line to gbest [B]"),
+            (Color::GREEN, "Cognitive pull (pbest)"),
+            (Color::FUCHSIA, "Social pull (gbest / lbest)"),
+            (Color::WHITE, "Resulting velocity"),
+        ],
+        Style {
+            top: Val::Px(40.0),
+            right: Val::Px(18.0),
+            ..default()
+        },
+    );
+
+    // Prompt saat target belum ada
+    commands.spawn((
+        TextBundle::from_section(
+            "Click to set a target\n(parameter keys already apply to the next swarm)",
+            TextStyle {
+                font_size: 22.0,
+                color: Color::rgb(1.0, 0.45, 0.45),
+                ..default()
+            },
+        )
+        .with_text_alignment(TextAlignment::Center)
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            top: Val::Percent(45.0),
+            left: Val::Percent(35.0),
+            ..default()
+        }),
+        TargetPromptText,
+    ));
+
     // FPS counter
     commands.spawn((
         TextBundle::from_section(
@@ -237,6 +860,113 @@ fn camera_controls(
     }
 }
 
+// [V]: kamera perspektif 3D <-> orthographic top-down; posisi kamera 3D
+// disimpan supaya bisa kembali ke sudut pandang sebelumnya.
+fn toggle_projection(
+    keyboard: Res<Input<KeyCode>>,
+    mut cameras: Query<(&mut Projection, &mut Transform), With<Camera3d>>,
+    mut saved: Local<Option<Transform>>,
+) {
+    if !keyboard.just_pressed(KeyCode::V) {
+        return;
+    }
+    for (mut projection, mut transform) in cameras.iter_mut() {
+        if let Projection::Perspective(_) = *projection {
+            *saved = Some(*transform);
+            *projection = Projection::Orthographic(OrthographicProjection {
+                scaling_mode: ScalingMode::FixedVertical(DOMAIN * 2.0 + 4.0),
+                ..default()
+            });
+            *transform = Transform::from_xyz(0.0, 60.0, 0.0).looking_at(Vec3::ZERO, Vec3::NEG_Z);
+        } else {
+            *projection = Projection::Perspective(default());
+            if let Some(saved) = saved.take() {
+                *transform = saved;
+            }
+        }
+    }
+}
+
+// Klik kanan: pilih partikel terdekat dari titik di lantai (klik jauh = batal)
+fn select_particle(
+    windows: Query<&Window>,
+    mouse: Res<Input<MouseButton>>,
+    camera_query: Query<(&Camera, &GlobalTransform)>,
+    pso: Res<PsoState>,
+    mut selected: ResMut<SelectedParticle>,
+) {
+    if !mouse.just_pressed(MouseButton::Right) {
+        return;
+    }
+    let Some(cursor) = windows.single().cursor_position() else {
+        return;
+    };
+    let (camera, camera_transform) = camera_query.single();
+    let Some(ray) = camera.viewport_to_world(camera_transform, cursor) else {
+        return;
+    };
+    let t = -ray.origin.y / ray.direction.y;
+    let pos = ray.origin + ray.direction * t;
+    let click = Vec2::new(pos.x, pos.z);
+
+    selected.0 = pso
+        .particles
+        .iter()
+        .enumerate()
+        .map(|(i, part)| {
+            let view = to_view(part.position, pso.params.log_view);
+            (i, view.distance(click))
+        })
+        .filter(|&(_, dist)| dist < PARTICLE_SIZE * 3.0)
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+        .map(|(i, _)| i);
+}
+
+fn draw_arrow(gizmos: &mut Gizmos, start: Vec3, vector: Vec3, color: Color) {
+    let end = start + vector;
+    gizmos.line(start, end, color);
+    let Some(dir) = vector.try_normalize() else {
+        return;
+    };
+    let side = dir.cross(Vec3::Y) * 0.4;
+    gizmos.line(end, end - dir * 0.8 + side, color);
+    gizmos.line(end, end - dir * 0.8 - side, color);
+}
+
+// Dekomposisi update velocity partikel terpilih:
+// hijau = cognitive (ke pbest), magenta = social (ke gbest), putih = velocity baru
+fn draw_particle_forces(
+    pso: Res<PsoState>,
+    selected: Res<SelectedParticle>,
+    mut gizmos: Gizmos,
+    mut text_query: Query<&mut Text, With<ForceText>>,
+) {
+    let part = selected.0.and_then(|i| pso.particles.get(i));
+    for mut text in text_query.iter_mut() {
+        text.sections[0].value = match part {
+            Some(part) => format!(
+                "Selected particle  |  cognitive (green) {:.2}  +  social (magenta) {:.2}  +  inertia {:.2}  =  velocity (white) {:.2}",
+                part.cognitive.length(),
+                part.social.length(),
+                (part.velocity - part.cognitive - part.social).length(),
+                part.velocity.length()
+            ),
+            None => String::new(),
+        };
+    }
+    let Some(part) = part else {
+        return;
+    };
+
+    // Panah tetap dalam skala linear, hanya pangkalnya ikut tampilan log
+    let view = to_view(part.position, pso.params.log_view);
+    let start = Vec3::new(view.x, 1.0, view.y);
+    let to_3d = |v: Vec2| Vec3::new(v.x, 0.0, v.y);
+    draw_arrow(&mut gizmos, start, to_3d(part.cognitive), Color::GREEN);
+    draw_arrow(&mut gizmos, start, to_3d(part.social), Color::FUCHSIA);
+    draw_arrow(&mut gizmos, start, to_3d(part.velocity), Color::WHITE);
+}
+
 fn mouse_set_target(
     mut click_marker: ResMut<ClickMarker>,
     windows: Query<&Window>,
@@ -245,9 +975,8 @@ fn mouse_set_target(
     mut commands: Commands,
     mut pso: ResMut<PsoState>,
     particles_query: Query<Entity, With<ParticleMarker>>,
-    target_entity: Query<Entity, With<TargetMarker>>,
-    mut meshes: ResMut<Assets<Mesh>>,
-    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut target_marker: Query<(&mut Transform, &mut Visibility), With<TargetMarker>>,
+    particle_assets: Res<ParticleAssets>,
 ) {
     let window = windows.single();
     if mouse.just_pressed(MouseButton::Left) {
@@ -256,66 +985,66 @@ fn mouse_set_target(
             if let Some(ray) = camera.viewport_to_world(camera_transform, cursor) {
                 let t = -ray.origin.y / ray.direction.y;
                 let pos = ray.origin + ray.direction * t;
-                let pos2d = Vec2::new(pos.x, pos.z);
+                let pos2d = from_view(Vec2::new(pos.x, pos.z), pso.params.log_view);
                 click_marker.0 = Some(pos2d);
 
-                // Target marker
-                let mark_color = Color::rgb(1.0, 0.15, 0.15);
-                if let Ok(e) = target_entity.get_single() {
-                    commands
-                        .entity(e)
-                        .insert(Transform::from_xyz(pos2d.x, 1.1, pos2d.y));
-                } else {
-                    commands.spawn((
-                        PbrBundle {
-                            mesh: meshes.add(Mesh::from(shape::UVSphere {
-                                radius: TARGET_SIZE,
-                                sectors: 20,
-                                stacks: 20,
-                            })),
-                            material: materials.add(StandardMaterial {
-                                base_color: mark_color,
-                                emissive: mark_color,
-                                ..default()
-                            }),
-                            transform: Transform::from_xyz(pos2d.x, 1.1, pos2d.y),
-                            ..default()
-                        },
-                        TargetMarker,
-                    ));
-                }
-
-                // Despawn old particles
-                for e in particles_query.iter() {
-                    commands.entity(e).despawn_recursive();
+                // Target marker langsung ke titik klik
+                for (mut transform, mut visibility) in target_marker.iter_mut() {
+                    *transform = Transform::from_xyz(pos.x, 1.1, pos.z);
+                    *visibility = Visibility::Inherited;
                 }
 
+                // Klik selalu kembali ke mode target, juga dari objective benchmark
+                pso.objective = Objective::Target {
+                    goal: pos2d,
+                    metric: pso.params.metric,
+                };
                 pso.target = Some(pos2d);
-                pso.paused = true;
-                pso.converged = false;
-                pso.current_gen = 0;
-                pso.gbest_val = f32::INFINITY;
-                pso.particles = init_population(&pso.params);
-                render_particles(&mut commands, &mut meshes, &mut materials, &pso.particles);
+                pso.target_origin = pos2d;
+                restart_swarm(&mut pso, &mut commands, &particle_assets, &particles_query);
             }
         }
     }
 }
 
+// Swarm baru (paused) untuk target/objective saat ini, partikel lama di-despawn.
+// Tanpa target swarm dibiarkan kosong sampai ada klik.
+fn restart_swarm(
+    pso: &mut PsoState,
+    commands: &mut Commands,
+    particle_assets: &ParticleAssets,
+    particles_query: &Query<Entity, With<ParticleMarker>>,
+) {
+    for e in particles_query.iter() {
+        commands.entity(e).despawn_recursive();
+    }
+    pso.paused = true;
+    pso.converged = false;
+    pso.current_gen = 0;
+    pso.history.clear();
+    pso.gbest_val = f32::INFINITY;
+    pso.particles = if pso.target.is_some() {
+        init_population(&pso.params)
+    } else {
+        Vec::new()
+    };
+    render_particles(commands, particle_assets, &pso.particles, &pso.params);
+}
+
 fn init_population(params: &PsoParams) -> Vec<Particle> {
     let mut rng = rand::thread_rng();
     (0..params.population)
         .map(|_| {
-            let pos = Vec2::new(
-                rng.gen_range(-DOMAIN..DOMAIN),
-                rng.gen_range(-DOMAIN..DOMAIN),
-            );
+            let pos = params.init.sample(&mut rng);
             Particle {
                 position: pos,
                 target_position: pos,
+                pos,
                 velocity: Vec2::ZERO,
                 pbest_pos: pos,
                 pbest_val: f32::INFINITY,
+                cognitive: Vec2::ZERO,
+                social: Vec2::ZERO,
             }
         })
         .collect()
@@ -323,25 +1052,18 @@ fn init_population(params: &PsoParams) -> Vec<Particle> {
 
 fn render_particles(
     commands: &mut Commands,
-    meshes: &mut ResMut<Assets<Mesh>>,
-    materials: &mut ResMut<Assets<StandardMaterial>>,
+    assets: &ParticleAssets,
     particles: &[Particle],
+    params: &PsoParams,
 ) {
     for (i, part) in particles.iter().enumerate() {
-        let hue = i as f32 / particles.len() as f32;
+        let view = to_view(part.position, params.log_view);
+        let (mesh, material) = assets.handles(i, particles.len(), params.sprites);
         commands.spawn((
             PbrBundle {
-                mesh: meshes.add(Mesh::from(shape::UVSphere {
-                    radius: PARTICLE_SIZE,
-                    sectors: 14,
-                    stacks: 14,
-                })),
-                material: materials.add(StandardMaterial {
-                    base_color: Color::hsl(200.0 + hue * 120.0, 0.8, 0.65),
-                    emissive: Color::rgb(0.1, 0.2, 0.5),
-                    ..default()
-                }),
-                transform: Transform::from_xyz(part.position.x, 1.0, part.position.y),
+                mesh: mesh.clone(),
+                material: material.clone(),
+                transform: Transform::from_xyz(view.x, 1.0, view.y),
                 ..default()
             },
             ParticleMarker(i),
@@ -349,40 +1071,264 @@ fn render_particles(
     }
 }
 
+// Geser `from` ke arah `to` paling jauh `max_step`
+fn clamp_jump(from: Vec2, to: Vec2, max_step: f32) -> Vec2 {
+    from + (to - from).clamp_length_max(max_step)
+}
+
 // SMOOTH INTERPOLATION HERE!
+type ParticleVisualItem<'a> = (
+    &'a ParticleMarker,
+    &'a mut Transform,
+    &'a mut Handle<Mesh>,
+    &'a mut Handle<StandardMaterial>,
+);
+
 fn update_particles_visual(
-    mut particles_query: Query<(&ParticleMarker, &mut Transform)>,
+    mut particles_query: Query<ParticleVisualItem, Without<Camera3d>>,
     mut pso: ResMut<PsoState>,
     time: Res<Time>,
+    assets: Res<ParticleAssets>,
+    cameras: Query<&Transform, With<Camera3d>>,
 ) {
-    for (marker, mut transform) in particles_query.iter_mut() {
+    // Setelah konvergen tidak ada generasi baru, jadi target visual
+    // dikejar pelan-pelan dengan laju yang sama seperti per generasi.
+    let catch_up = pso.converged && pso.params.clamp_jumps;
+    let catch_up_step = MAX_VISUAL_JUMP * time.delta_seconds() / GEN_INTERVAL as f32;
+    let log_view = pso.params.log_view;
+    let sprites = pso.params.sprites;
+    let count = pso.particles.len();
+    // Billboard: quad menghadap kamera (rotasi sama dengan kamera)
+    let facing = cameras
+        .get_single()
+        .map_or(Quat::IDENTITY, |camera| camera.rotation);
+
+    for (marker, mut transform, mut mesh, mut material) in particles_query.iter_mut() {
+        // Ganti representasi di tempat saat [T] ditekan, tanpa spawn ulang
+        let (wanted_mesh, wanted_material) = assets.handles(marker.0, count, sprites);
+        if *mesh != *wanted_mesh {
+            *mesh = wanted_mesh.clone();
+            *material = wanted_material.clone();
+        }
+        transform.rotation = if sprites { facing } else { Quat::IDENTITY };
+
         if let Some(part) = pso.particles.get_mut(marker.0) {
+            if catch_up {
+                part.target_position = clamp_jump(part.target_position, part.pos, catch_up_step);
+            }
+
             // Lerp dari position ke target_position
             part.position = part
                 .position
                 .lerp(part.target_position, LERP_SPEED * time.delta_seconds());
 
-            transform.translation.x = part.position.x;
-            transform.translation.z = part.position.y;
+            // Lerp tetap di ruang linear; transform log hanya saat ditulis ke dunia
+            let view = to_view(part.position, log_view);
+            transform.translation.x = view.x;
+            transform.translation.z = view.y;
         }
     }
 }
 
+fn draw_gbest_lines(
+    keyboard: Res<Input<KeyCode>>,
+    pso: Res<PsoState>,
+    mut lines: ResMut<GbestLines>,
+    mut gizmos: Gizmos,
+    time: Res<Time>,
+) {
+    if keyboard.just_pressed(KeyCode::B) {
+        lines.show = !lines.show;
+    }
+
+    // gbest belum ada sebelum generasi pertama
+    if pso.target.is_none() || !pso.gbest_val.is_finite() {
+        lines.visual = None;
+        return;
+    }
+    let gbest = match lines.visual {
+        Some(visual) => visual.lerp(pso.gbest_pos, LERP_SPEED * time.delta_seconds()),
+        None => pso.gbest_pos,
+    };
+    lines.visual = Some(gbest);
+
+    if !lines.show {
+        return;
+    }
+    let log_view = pso.params.log_view;
+    let gbest = to_view(gbest, log_view);
+    let end = Vec3::new(gbest.x, 1.0, gbest.y);
+    for part in &pso.particles {
+        let view = to_view(part.position, log_view);
+        let start = Vec3::new(view.x, 1.0, view.y);
+        gizmos.line(start, end, Color::rgba(1.0, 0.85, 0.3, 0.25));
+    }
+}
+
 fn update_generation_text(mut text_query: Query<&mut Text, With<GenText>>, pso: Res<PsoState>) {
     let mut text = text_query.single_mut();
     let params = &pso.params;
     text.sections[0].value = format!(
-        "Gen: {}/{}  |  Pop: {}  |  w: {:.2}  c1: {:.2}  c2: {:.2}  {}",
+        "Gen: {}/{}{}  |  Pop: {}  |  w: {:.2}{}  c1: {:.2}  c2: {:.2}  v_max: {:.1}  |  {}  |  {}  |  {} init{}{}{}  {}",
         pso.current_gen,
         params.generations,
+        if pso.current_gen > params.generations {
+            " (refining)"
+        } else {
+            ""
+        },
         params.population,
-        params.w,
+        params.effective_w(pso.current_gen),
+        if params.anneal_w { " (annealed)" } else { "" },
         params.c1,
         params.c2,
-        if pso.converged { " ✅ CONVERGED!" } else { "" }
+        params.v_max,
+        params.topology.name(),
+        if matches!(pso.objective, Objective::Target { .. }) {
+            params.metric.name()
+        } else {
+            pso.objective.name()
+        },
+        params.init.name(),
+        if params.clamp_jumps { "  |  clamp" } else { "" },
+        if params.log_view { "  |  log view" } else { "" },
+        if !pso.drifting() {
+            String::new()
+        } else {
+            format!("  |  {}", params.drift.name())
+        },
+        if pso.converged && pso.drifting() {
+            " 🎯 ON TARGET"
+        } else if pso.converged {
+            " ✅ CONVERGED!"
+        } else {
+            ""
+        }
     );
 }
 
+// Legenda warna: kotak warna + keterangan per baris. `position` hanya
+// mengisi top/left/right/bottom, sisanya diatur di sini.
+fn spawn_legend(commands: &mut Commands, title: &str, entries: &[(Color, &str)], position: Style) {
+    let text_style = |font_size| TextStyle {
+        font_size,
+        color: Color::WHITE,
+        ..default()
+    };
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                flex_direction: FlexDirection::Column,
+                padding: UiRect::all(Val::Px(8.0)),
+                row_gap: Val::Px(4.0),
+                ..position
+            },
+            background_color: Color::rgba(0.0, 0.0, 0.0, 0.6).into(),
+            ..default()
+        })
+        .with_children(|parent| {
+            parent.spawn(TextBundle::from_section(title, text_style(15.0)));
+            for &(color, label) in entries {
+                parent
+                    .spawn(NodeBundle {
+                        style: Style {
+                            align_items: AlignItems::Center,
+                            column_gap: Val::Px(6.0),
+                            ..default()
+                        },
+                        ..default()
+                    })
+                    .with_children(|row| {
+                        row.spawn(NodeBundle {
+                            style: Style {
+                                width: Val::Px(14.0),
+                                height: Val::Px(14.0),
+                                ..default()
+                            },
+                            background_color: color.into(),
+                            ..default()
+                        });
+                        row.spawn(TextBundle::from_section(label, text_style(14.0)));
+                    });
+            }
+        });
+}
+
+// Titik tengah posisi visual partikel
+fn swarm_centroid(particles: &[Particle]) -> Option<Vec2> {
+    if particles.is_empty() {
+        return None;
+    }
+    let sum: Vec2 = particles.iter().map(|part| part.position).sum();
+    Some(sum / particles.len() as f32)
+}
+
+// Proyeksikan titik di atas centroid ke layar supaya label selalu menghadap kamera
+fn update_swarm_label(
+    pso: Res<PsoState>,
+    camera_query: Query<(&Camera, &GlobalTransform)>,
+    mut label_query: Query<(&mut Text, &mut Style, &mut Visibility), With<SwarmLabel>>,
+) {
+    let (camera, camera_transform) = camera_query.single();
+    let screen_pos = swarm_centroid(&pso.particles).and_then(|centroid| {
+        let centroid = to_view(centroid, pso.params.log_view);
+        camera.world_to_viewport(camera_transform, Vec3::new(centroid.x, 3.0, centroid.y))
+    });
+
+    for (mut text, mut style, mut visibility) in label_query.iter_mut() {
+        let Some(screen_pos) = screen_pos else {
+            *visibility = Visibility::Hidden;
+            continue;
+        };
+        text.sections[0].value = if pso.gbest_val.is_finite() {
+            format!("Gen {}  |  gbest {:.2}", pso.current_gen, pso.gbest_val)
+        } else {
+            format!("Gen {}", pso.current_gen)
+        };
+        // Kira-kira di tengah teks
+        style.left = Val::Px(screen_pos.x - 60.0);
+        style.top = Val::Px(screen_pos.y - 10.0);
+        *visibility = Visibility::Inherited;
+    }
+}
+
+// Ringkasan swarm terakhir saat window ditutup
+fn print_summary_on_exit(mut exit_events: EventReader<AppExit>, pso: Res<PsoState>) {
+    if exit_events.iter().next().is_none() {
+        return;
+    }
+
+    println!("\n=== Run summary ===");
+    if pso.target.is_some() && pso.gbest_val.is_finite() {
+        println!(
+            "Best fitness: {:.3} ({})",
+            pso.gbest_val,
+            pso.objective.name()
+        );
+        println!(
+            "Generations used: {}/{}",
+            pso.current_gen, pso.params.generations
+        );
+    } else {
+        println!("No swarm was run");
+    }
+    println!("===================");
+}
+
+fn update_target_prompt(
+    pso: Res<PsoState>,
+    mut query: Query<&mut Visibility, With<TargetPromptText>>,
+) {
+    for mut visibility in query.iter_mut() {
+        *visibility = if pso.target.is_none() {
+            Visibility::Inherited
+        } else {
+            Visibility::Hidden
+        };
+    }
+}
+
 fn update_fps_text(
     diagnostics: Res<bevy::diagnostic::DiagnosticsStore>,
     mut query: Query<&mut Text, With<FpsText>>,
@@ -396,47 +1342,88 @@ fn update_fps_text(
     }
 }
 
-fn pso_tick(time: Res<Time>, keyboard: Res<Input<KeyCode>>, mut pso: ResMut<PsoState>) {
-    if pso.target.is_none() || pso.converged {
+fn pso_tick(
+    time: Res<Time>,
+    keyboard: Res<Input<KeyCode>>,
+    mut pso: ResMut<PsoState>,
+    mut since_gen: Local<f64>,
+) {
+    if pso.target.is_none() {
         return;
     }
+    // Target yang drift terus dikejar sampai batas generasi
+    let drifting = pso.drifting();
+    let finished = (pso.converged && !drifting) || pso.current_gen >= pso.params.generations;
 
+    // Setelah selesai, [G] menjalankan satu generasi penyempurnaan manual
+    // (auto tetap mati); klik target baru mereset semuanya.
     let mut advance = false;
     if keyboard.just_pressed(KeyCode::G) {
         advance = true;
-        pso.paused = false;
+        pso.paused = finished;
+        if finished {
+            println!(
+                "Refinement generation {} (gbest {:.3})",
+                pso.current_gen + 1,
+                pso.gbest_val
+            );
+        }
     }
-    if keyboard.just_pressed(KeyCode::P) {
+    if keyboard.just_pressed(KeyCode::P) && !finished {
         pso.paused = !pso.paused;
     }
 
-    // Update tiap 0.3 detik untuk smooth animation
-    if !pso.paused && (time.elapsed_seconds_f64() % 0.3 < 0.02) {
+    // Update tiap 0.3 detik untuk smooth animation. Akumulasi delta (bukan
+    // elapsed % interval) supaya tidak ada generasi terlewat saat time scale 4x.
+    *since_gen += time.delta_seconds_f64();
+    if !finished && !pso.paused && *since_gen >= GEN_INTERVAL {
         advance = true;
     }
 
     if !advance {
         return;
     }
+    *since_gen = 0.0;
 
     // Copy params untuk avoid borrow issue
     let params = pso.params;
-    let goal = pso.target.unwrap();
+    let mut rng = rand::thread_rng();
+    let mut goal = pso.target.unwrap();
+    if drifting {
+        goal = params
+            .drift
+            .advance(goal, pso.target_origin, pso.current_gen, &mut rng);
+        pso.target = Some(goal);
+    }
+    // Mode target mengikuti titik (yang mungkin bergeser) dan metric aktif
+    if let Objective::Target {
+        goal: target,
+        metric,
+    } = &mut pso.objective
+    {
+        *target = goal;
+        *metric = params.metric;
+    }
+    let objective = pso.objective;
 
     // 1. Update pbest & gbest
     let mut global_best_val = f32::INFINITY;
     let mut global_best_pos = Vec2::ZERO;
 
     for part in &mut pso.particles {
-        // Use target_position untuk fitness (posisi sebenarnya dalam algoritma)
-        let dist = (part.target_position - goal).length();
+        // pbest lama dinilai ulang terhadap target yang sudah bergeser
+        if drifting {
+            part.pbest_val = objective.eval(part.pbest_pos);
+        }
+        // Fitness pakai pos (posisi sebenarnya dalam algoritma), bukan posisi visual
+        let dist = objective.eval(part.pos);
         if dist < part.pbest_val {
-            part.pbest_pos = part.target_position;
+            part.pbest_pos = part.pos;
             part.pbest_val = dist;
         }
         if dist < global_best_val {
             global_best_val = dist;
-            global_best_pos = part.target_position;
+            global_best_pos = part.pos;
         }
     }
 
@@ -444,38 +1431,219 @@ fn pso_tick(time: Res<Time>, keyboard: Res<Input<KeyCode>>, mut pso: ResMut<PsoS
     pso.gbest_pos = global_best_pos;
 
     // 2. Update velocity & target_position
-    let mut rng = rand::thread_rng();
-    for part in &mut pso.particles {
+    let w = params.effective_w(pso.current_gen);
+    let social_best: Vec<Vec2> = (0..pso.particles.len())
+        .map(|i| {
+            params
+                .topology
+                .social_best(&pso.particles, i, global_best_pos)
+        })
+        .collect();
+    for (part, social_best) in pso.particles.iter_mut().zip(social_best) {
         let r1 = rng.gen_range(0.0..1.0);
         let r2 = rng.gen_range(0.0..1.0);
 
-        part.velocity = params.w * part.velocity
-            + params.c1 * r1 * (part.pbest_pos - part.target_position)
-            + params.c2 * r2 * (global_best_pos - part.target_position);
+        part.cognitive = params.c1 * r1 * (part.pbest_pos - part.pos);
+        part.social = params.c2 * r2 * (social_best - part.pos);
+        part.velocity =
+            (w * part.velocity + part.cognitive + part.social).clamp_length_max(params.v_max);
 
-        let mut new_pos = part.target_position + part.velocity;
+        let mut new_pos = part.pos + part.velocity;
         new_pos.x = new_pos.x.clamp(-DOMAIN, DOMAIN);
         new_pos.y = new_pos.y.clamp(-DOMAIN, DOMAIN);
+        part.pos = new_pos;
 
-        part.target_position = new_pos; // Set target untuk lerp
+        // Set target untuk lerp (dibatasi kalau clamp aktif, murni visual)
+        part.target_position = if params.clamp_jumps {
+            clamp_jump(part.target_position, new_pos, MAX_VISUAL_JUMP)
+        } else {
+            new_pos
+        };
     }
 
     pso.current_gen += 1;
+    let gbest_val = pso.gbest_val;
+    pso.history.push(gbest_val);
 
-    if pso.current_gen >= params.generations || pso.gbest_val < 0.7 {
+    // Semua objective punya minimum global 0
+    let on_target = pso.gbest_val < objective.tolerance();
+    if drifting {
+        pso.converged = on_target;
+        if pso.current_gen >= params.generations {
+            pso.paused = true;
+        }
+    } else if pso.current_gen >= params.generations || on_target {
+        // Generasi refinement tidak dihitung sebagai run baru
+        if !finished {
+            println!(
+                "💡 {}",
+                tuning_hint(&pso.history, &params, objective.tolerance())
+            );
+        }
         pso.converged = true;
         pso.paused = true;
     }
 }
 
+// Saran parameter sederhana dari riwayat gbest satu run (tanpa drift)
+fn tuning_hint(history: &[f32], params: &PsoParams, tolerance: f32) -> String {
+    let gens = history.len();
+    let Some(&last) = history.last() else {
+        return "No generations ran".to_string();
+    };
+
+    if last < tolerance {
+        if (gens as f32) < params.generations as f32 * EARLY_CONVERGE_FRACTION {
+            return format!(
+                "Converged fast (gen {}/{}): try lower w or fewer generations",
+                gens, params.generations
+            );
+        }
+        return format!(
+            "Converged at gen {}/{}: parameters look balanced",
+            gens, params.generations
+        );
+    }
+
+    // Tidak sampai: bedakan swarm yang macet dengan yang masih membaik
+    let tail_start = history[gens - gens.div_ceil(3)];
+    if tail_start - last < tail_start * STAGNATION_GAIN {
+        format!(
+            "Never converged, stalled at {:.2}: try higher c2 or higher w to escape",
+            last
+        )
+    } else {
+        format!(
+            "Never converged but still improving ({:.2} -> {:.2}): try higher c2 or more generations",
+            tail_start, last
+        )
+    }
+}
+
+// Marker target mengikuti pso.target (bergerak kalau drift aktif); muncul
+// langsung di tempatnya kalau sebelumnya tersembunyi
+fn sync_target_marker(
+    time: Res<Time>,
+    pso: Res<PsoState>,
+    mut marker: Query<(&mut Transform, &mut Visibility), With<TargetMarker>>,
+) {
+    for (mut transform, mut visibility) in marker.iter_mut() {
+        let Some(target) = pso.target else {
+            *visibility = Visibility::Hidden;
+            continue;
+        };
+        let target = to_view(target, pso.params.log_view);
+        let goal = Vec3::new(target.x, 1.1, target.y);
+        if *visibility == Visibility::Hidden {
+            transform.translation = goal;
+            *visibility = Visibility::Inherited;
+        } else {
+            transform.translation = transform
+                .translation
+                .lerp(goal, (LERP_SPEED * time.delta_seconds()).min(1.0));
+        }
+    }
+}
+
+// Heightfield dibangun ulang saat objective benchmark atau tampilan log berganti;
+// mode target tidak punya landscape
+fn update_landscape(
+    pso: Res<PsoState>,
+    mut built: Local<Option<(Objective, bool)>>,
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    landscapes: Query<Entity, With<Landscape>>,
+) {
+    let objective = match pso.objective {
+        // Goal target berubah tiap drift, tapi tidak memengaruhi landscape
+        Objective::Target { metric, .. } => Objective::Target {
+            goal: Vec2::ZERO,
+            metric,
+        },
+        objective => objective,
+    };
+    let key = (objective, pso.params.log_view);
+    if *built == Some(key) {
+        return;
+    }
+    *built = Some(key);
+
+    for e in landscapes.iter() {
+        commands.entity(e).despawn_recursive();
+    }
+    if matches!(objective, Objective::Target { .. }) {
+        return;
+    }
+    commands.spawn((
+        PbrBundle {
+            mesh: meshes.add(landscape_mesh(&objective, pso.params.log_view)),
+            material: materials.add(StandardMaterial {
+                base_color: Color::WHITE,
+                perceptual_roughness: 0.9,
+                ..default()
+            }),
+            ..default()
+        },
+        Landscape,
+    ));
+}
+
+// Grid di ruang dunia; tinggi & warna dari log(1 + f) supaya lembah Rastrigin
+// tetap terlihat di samping lereng Rosenbrock yang sangat curam.
+// Lembah (biru) paling bawah, puncak (merah) tepat di bawah bidang partikel.
+fn landscape_mesh(objective: &Objective, log_view: bool) -> Mesh {
+    let n = LANDSCAPE_RESOLUTION;
+    let step = 2.0 * DOMAIN / n as f32;
+    let mut samples = Vec::with_capacity((n + 1) * (n + 1));
+    for row in 0..=n {
+        for col in 0..=n {
+            let view = Vec2::new(-DOMAIN + col as f32 * step, -DOMAIN + row as f32 * step);
+            samples.push((view, objective.eval(from_view(view, log_view))));
+        }
+    }
+    let peak = samples.iter().map(|s| s.1).fold(0.0, f32::max).ln_1p();
+    let (positions, colors): (Vec<[f32; 3]>, Vec<[f32; 4]>) = samples
+        .iter()
+        .map(|&(view, val)| {
+            let t = val.ln_1p() / peak;
+            (
+                [view.x, LANDSCAPE_DEPTH * (t - 1.0), view.y],
+                Color::hsl(240.0 * (1.0 - t), 0.7, 0.45).as_rgba_f32(),
+            )
+        })
+        .unzip();
+
+    let width = (n + 1) as u32;
+    let mut indices = Vec::with_capacity(n * n * 6);
+    for row in 0..n as u32 {
+        for col in 0..n as u32 {
+            let i = row * width + col;
+            indices.extend([i, i + width, i + 1, i + 1, i + width, i + width + 1]);
+        }
+    }
+
+    let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_COLOR, colors);
+    mesh.set_indices(Some(Indices::U32(indices)));
+    mesh.duplicate_vertices();
+    mesh.compute_flat_normals();
+    mesh
+}
+
 fn update_ui_sliders(
     keyboard: Res<Input<KeyCode>>,
     mut pso: ResMut<PsoState>,
     mut commands: Commands,
-    mut meshes: ResMut<Assets<Mesh>>,
-    mut materials: ResMut<Assets<StandardMaterial>>,
+    particle_assets: Res<ParticleAssets>,
     particles_query: Query<Entity, With<ParticleMarker>>,
 ) {
+    let pop_step = if keyboard.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]) {
+        POP_STEP_FAST
+    } else {
+        1
+    };
     if keyboard.just_pressed(KeyCode::Equals) {
         pso.params.generations += 2;
     }
@@ -483,10 +1651,10 @@ fn update_ui_sliders(
         pso.params.generations = pso.params.generations.saturating_sub(2);
     }
     if keyboard.just_pressed(KeyCode::U) {
-        pso.params.population += 1;
+        pso.params.population = (pso.params.population + pop_step).min(MAX_POPULATION);
     }
     if keyboard.just_pressed(KeyCode::J) {
-        pso.params.population = pso.params.population.saturating_sub(1).max(3);
+        pso.params.population = pso.params.population.saturating_sub(pop_step).max(3);
     }
     if keyboard.just_pressed(KeyCode::I) {
         pso.params.w = (pso.params.w + 0.05).min(1.2);
@@ -494,6 +1662,9 @@ fn update_ui_sliders(
     if keyboard.just_pressed(KeyCode::K) {
         pso.params.w = (pso.params.w - 0.05).max(0.0);
     }
+    if keyboard.just_pressed(KeyCode::Comma) {
+        pso.params.anneal_w = !pso.params.anneal_w;
+    }
     if keyboard.just_pressed(KeyCode::O) {
         pso.params.c1 += 0.1;
     }
@@ -506,18 +1677,254 @@ fn update_ui_sliders(
     if keyboard.just_pressed(KeyCode::Semicolon) {
         pso.params.c2 = (pso.params.c2 - 0.1).max(0.0);
     }
+    if keyboard.just_pressed(KeyCode::Y) {
+        pso.params.v_max = (pso.params.v_max + V_MAX_STEP).min(2.0 * DOMAIN);
+    }
+    if keyboard.just_pressed(KeyCode::H) {
+        pso.params.v_max = (pso.params.v_max - V_MAX_STEP).max(V_MAX_STEP);
+    }
+    if keyboard.just_pressed(KeyCode::C) {
+        pso.params.clamp_jumps = !pso.params.clamp_jumps;
+    }
+    if keyboard.just_pressed(KeyCode::M) {
+        pso.params.metric = pso.params.metric.next();
+    }
+    if keyboard.just_pressed(KeyCode::Slash) {
+        pso.params.topology = pso.params.topology.next();
+    }
+    if keyboard.just_pressed(KeyCode::Z) {
+        pso.params.init = pso.params.init.next();
+    }
+    if keyboard.just_pressed(KeyCode::X) {
+        pso.params.drift = pso.params.drift.next();
+    }
+    if keyboard.just_pressed(KeyCode::R) {
+        pso.params.log_view = !pso.params.log_view;
+    }
+    if keyboard.just_pressed(KeyCode::T) {
+        pso.params.sprites = !pso.params.sprites;
+    }
+
+    if keyboard.just_pressed(KeyCode::F) {
+        match pso.objective.next_benchmark() {
+            // Optimum benchmark menggantikan titik klik, swarm langsung siap
+            Some(objective) => {
+                pso.objective = objective;
+                pso.target = Some(objective.optimum());
+                pso.target_origin = objective.optimum();
+                restart_swarm(&mut pso, &mut commands, &particle_assets, &particles_query);
+            }
+            // Kembali ke mode klik: tunggu target baru
+            None => {
+                pso.objective = Objective::Target {
+                    goal: Vec2::ZERO,
+                    metric: pso.params.metric,
+                };
+                pso.target = None;
+                restart_swarm(&mut pso, &mut commands, &particle_assets, &particles_query);
+            }
+        }
+        println!("🎯 Objective: {}", pso.objective.name());
+    }
 
     if keyboard.just_pressed(KeyCode::N) {
-        pso.paused = true;
-        pso.converged = false;
-        pso.current_gen = 0;
-        pso.gbest_val = f32::INFINITY;
+        // Drift dimulai lagi dari titik klik
         if pso.target.is_some() {
-            for e in particles_query.iter() {
-                commands.entity(e).despawn_recursive();
+            pso.target = Some(pso.target_origin);
+        }
+        restart_swarm(&mut pso, &mut commands, &particle_assets, &particles_query);
+    }
+}
+
+// --- BENCHMARK GA vs PSO ---
+// Keduanya dihentikan pada jumlah evaluasi fitness yang sama (bukan jumlah
+// generasi), atau lebih awal kalau sudah dalam CONVERGE_TOLERANCE dari target.
+
+const COMPARE_RUNS: u64 = 10;
+const GA_TOURNAMENT: usize = 3;
+const GA_MUTATION_RATE: f32 = 0.2;
+const GA_MUTATION_STEP: f32 = 3.0;
+
+// Menghitung evaluasi; None berarti budget habis
+struct Evaluator {
+    goal: Vec2,
+    metric: Metric,
+    budget: usize,
+    count: usize,
+    best: f32,
+}
+
+impl Evaluator {
+    fn new(goal: Vec2, metric: Metric, budget: usize) -> Self {
+        Self {
+            goal,
+            metric,
+            budget,
+            count: 0,
+            best: f32::INFINITY,
+        }
+    }
+
+    fn eval(&mut self, pos: Vec2) -> Option<f32> {
+        if self.done() {
+            return None;
+        }
+        self.count += 1;
+        let val = self.metric.distance(pos, self.goal);
+        self.best = self.best.min(val);
+        Some(val)
+    }
+
+    fn done(&self) -> bool {
+        self.count >= self.budget || self.best < CONVERGE_TOLERANCE
+    }
+}
+
+fn random_point(rng: &mut StdRng) -> Vec2 {
+    Vec2::new(
+        rng.gen_range(-DOMAIN..DOMAIN),
+        rng.gen_range(-DOMAIN..DOMAIN),
+    )
+}
+
+fn run_pso_budget(params: &PsoParams, eval: &mut Evaluator, rng: &mut StdRng) {
+    let mut particles: Vec<Particle> = Vec::with_capacity(params.population);
+    let mut gbest_pos = Vec2::ZERO;
+    let mut gbest_val = f32::INFINITY;
+
+    for _ in 0..params.population {
+        let pos = params.init.sample(rng);
+        let Some(val) = eval.eval(pos) else { return };
+        if val < gbest_val {
+            gbest_val = val;
+            gbest_pos = pos;
+        }
+        particles.push(Particle {
+            position: pos,
+            target_position: pos,
+            pos,
+            velocity: Vec2::ZERO,
+            pbest_pos: pos,
+            pbest_val: val,
+            cognitive: Vec2::ZERO,
+            social: Vec2::ZERO,
+        });
+    }
+
+    while !eval.done() {
+        for part in &mut particles {
+            let r1 = rng.gen_range(0.0..1.0);
+            let r2 = rng.gen_range(0.0..1.0);
+            part.velocity = (params.w * part.velocity
+                + params.c1 * r1 * (part.pbest_pos - part.pos)
+                + params.c2 * r2 * (gbest_pos - part.pos))
+                .clamp_length_max(params.v_max);
+            part.pos = (part.pos + part.velocity).clamp(Vec2::splat(-DOMAIN), Vec2::splat(DOMAIN));
+
+            let Some(val) = eval.eval(part.pos) else {
+                return;
+            };
+            if val < part.pbest_val {
+                part.pbest_pos = part.pos;
+                part.pbest_val = val;
+            }
+            if val < gbest_val {
+                gbest_val = val;
+                gbest_pos = part.pos;
+            }
+        }
+    }
+}
+
+// GA real-valued: tournament selection, arithmetic crossover, mutasi uniform, elitism 1
+fn run_ga_budget(population: usize, eval: &mut Evaluator, rng: &mut StdRng) {
+    let mut pop: Vec<(Vec2, f32)> = Vec::with_capacity(population);
+    for _ in 0..population {
+        let pos = random_point(rng);
+        let Some(val) = eval.eval(pos) else { return };
+        pop.push((pos, val));
+    }
+
+    while !eval.done() {
+        let elite = *pop
+            .iter()
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .expect("population is not empty");
+        let mut next = vec![elite];
+
+        while next.len() < population {
+            let a = tournament(&pop, rng);
+            let b = tournament(&pop, rng);
+            let t = rng.gen_range(0.0..1.0);
+            let mut child = a.lerp(b, t);
+            if rng.gen_range(0.0..1.0) < GA_MUTATION_RATE {
+                child += Vec2::new(
+                    rng.gen_range(-GA_MUTATION_STEP..GA_MUTATION_STEP),
+                    rng.gen_range(-GA_MUTATION_STEP..GA_MUTATION_STEP),
+                );
             }
-            pso.particles = init_population(&pso.params);
-            render_particles(&mut commands, &mut meshes, &mut materials, &pso.particles);
+            let child = child.clamp(Vec2::splat(-DOMAIN), Vec2::splat(DOMAIN));
+            let Some(val) = eval.eval(child) else { return };
+            next.push((child, val));
         }
+        pop = next;
     }
 }
+
+fn tournament(pop: &[(Vec2, f32)], rng: &mut StdRng) -> Vec2 {
+    (0..GA_TOURNAMENT)
+        .map(|_| pop[rng.gen_range(0..pop.len())])
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+        .expect("tournament size > 0")
+        .0
+}
+
+fn run_comparison(budget: usize, seed: u64) {
+    let params = PsoParams::default();
+    println!(
+        "=== GA vs PSO | budget {} evals | pop {} | metric {} | seed {} ===",
+        budget,
+        params.population,
+        params.metric.name(),
+        seed
+    );
+    println!("run |   PSO best (evals) |    GA best (evals)");
+
+    let mut totals = [(0.0, 0usize); 2];
+    for run in 0..COMPARE_RUNS {
+        // Target dan kedua optimizer memakai seed yang sama per run
+        let run_seed = seed + run;
+        let goal = random_point(&mut StdRng::seed_from_u64(run_seed));
+
+        let mut pso_eval = Evaluator::new(goal, params.metric, budget);
+        run_pso_budget(
+            &params,
+            &mut pso_eval,
+            &mut StdRng::seed_from_u64(run_seed + 1),
+        );
+        let mut ga_eval = Evaluator::new(goal, params.metric, budget);
+        run_ga_budget(
+            params.population,
+            &mut ga_eval,
+            &mut StdRng::seed_from_u64(run_seed + 1),
+        );
+
+        println!(
+            "{:3} | {:10.3} ({:5}) | {:10.3} ({:5})",
+            run, pso_eval.best, pso_eval.count, ga_eval.best, ga_eval.count
+        );
+        for (total, eval) in totals.iter_mut().zip([&pso_eval, &ga_eval]) {
+            total.0 += eval.best;
+            total.1 += eval.count;
+        }
+    }
+
+    let runs = COMPARE_RUNS as f32;
+    println!(
+        "avg | {:10.3} ({:5.0}) | {:10.3} ({:5.0})",
+        totals[0].0 / runs,
+        totals[0].1 as f32 / runs,
+        totals[1].0 / runs,
+        totals[1].1 as f32 / runs
+    );
+}
@@ -1,37 +1,437 @@
+use bevy::app::AppExit;
+use bevy::diagnostic::{FrameTimeDiagnosticsPlugin, LogDiagnosticsPlugin};
+use bevy::input::mouse::MouseMotion;
+use bevy::input::InputSystem;
 use bevy::prelude::*;
+use bevy::render::camera::ScalingMode;
+use bevy::window::{CursorGrabMode, WindowMode};
 use rand::Rng;
 
 // Konstanta untuk mempermudah penyesuaian
 const PLAYER_SPEED: f32 = 5.0;
 const DESIRED_SEPARATION: f32 = 2.0; // Jarak minimal antar NPC
+const AGENT_RADIUS: f32 = 0.5; // Setengah ukuran kubus NPC
+const ARENA_HALF: f32 = 12.5; // Setengah ukuran lantai (25.0 / 2)
+const EXPLORE_GRID: usize = 5; // Grid kunjungan kasar EXPLORE_GRID x EXPLORE_GRID
+const FLOOR_GRID_SPACING: f32 = 1.0; // Jarak garis grid lantai ([G] untuk toggle)
+const COLLECTIBLE_COUNT: usize = 3; // Jumlah collectible aktif sekaligus (--collect)
+const COLLECT_RADIUS: f32 = 1.0; // Jarak sentuh untuk mengambil collectible
+const GUARD_WAYPOINT_RADIUS: f32 = 1.0; // Jarak dianggap sampai di waypoint patroli
+const FREE_FLY_SPEED: f32 = 10.0; // Kecepatan kamera free-fly ([Tab]), unit/detik
+const FREE_FLY_SENSITIVITY: f32 = 0.003; // Radian per piksel gerak mouse
+const SPEED_GLOW: f32 = 0.8; // Emissive maksimum saat agen di max_speed ([T] untuk toggle)
 
 fn main() {
-    App::new()
-        .add_plugins(DefaultPlugins)
-        .add_systems(Startup, setup)
-        .add_systems(
-            Update,
+    let mut app = App::new();
+    app.add_plugins(DefaultPlugins.set(WindowPlugin {
+        primary_window: Some(window_from_args("Bevy Steering AI")),
+        ..default()
+    }))
+    .init_resource::<LeaderIndex>()
+    .insert_resource(FloorGrid(true))
+    // Scene stress berbagi satu material, jadi glow per agen tidak berlaku di sana
+    .insert_resource(SpeedGlow(stress_grid_size().is_none()))
+    .add_systems(
+        Startup,
+        (setup, setup_score_hud.run_if(resource_exists::<Score>())),
+    )
+    .add_systems(Last, print_summary_on_exit)
+    .add_systems(PreUpdate, free_fly_camera_system.after(InputSystem))
+    .add_systems(
+        Update,
+        (
+            player_movement_system,
+            cycle_leader_system,
+            toggle_fullscreen,
+            time_scale_system,
+            toggle_projection,
+            floor_grid_system,
+            behavior_state_system,
+            leader_indicator_system,
             (
-                player_movement_system,
-                // Sistem-sistem ini akan menghitung gaya kemudi (steering force)
-                // dan langsung menerapkannya ke Velocity.
-                // .chain() memastikan mereka berjalan dalam urutan ini setiap frame.
-                (
-                    seek_system,
-                    flee_system,
-                    arrive_system,
-                    wander_system,
-                    pursuit_system,
-                    evade_system,
-                    separation_system,
-                    containment_system,
-                )
-                    .chain(),
-                // Sistem terakhir yang menerapkan hasil akhir Velocity ke posisi Transform.
-                movement_system,
-            ),
+                spawn_collectibles,
+                collectible_target_system,
+                collect_system,
+                score_text_system,
+            )
+                .chain()
+                .run_if(resource_exists::<Score>()),
+            // Sistem-sistem ini akan menghitung gaya kemudi (steering force)
+            // dan langsung menerapkannya ke Velocity.
+            // .chain() memastikan mereka berjalan dalam urutan ini setiap frame.
+            (
+                // Avoidance dijalankan paling awal supaya seek tahu
+                // apakah agen sedang menghindar di frame ini.
+                obstacle_avoidance_system,
+                seek_system,
+                flee_system,
+                arrive_system,
+                wander_system,
+                explore_bias_system,
+                pursuit_system,
+                evade_system,
+                guard_system,
+                follow_leader_system,
+                formation_system,
+                separation_system,
+                containment_system,
+            )
+                .chain(),
+            // Sistem terakhir yang menerapkan hasil akhir Velocity ke posisi Transform.
+            movement_system,
+            speed_glow_system.after(movement_system),
+        ),
+    );
+
+    // --collect N: mode game, pemain vs seeker berebut collectible, menang di N poin
+    if let Some(win_score) = arg_value("--collect").and_then(|v| v.parse().ok()) {
+        app.insert_resource(Score {
+            win_score,
+            ..default()
+        });
+    }
+
+    // Scene stress: log FPS ke terminal
+    if stress_grid_size().is_some() {
+        app.add_plugins((FrameTimeDiagnosticsPlugin, LogDiagnosticsPlugin::default()));
+    }
+    app.run();
+}
+
+fn arg_value(name: &str) -> Option<String> {
+    let args: Vec<String> = std::env::args().collect();
+    args.iter()
+        .position(|arg| arg == name)
+        .and_then(|i| args.get(i + 1).cloned())
+}
+
+// Window dari CLI: --width, --height (default 1280x720), --fullscreen
+fn window_from_args(title: &str) -> Window {
+    let width = arg_value("--width")
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(1280.0);
+    let height = arg_value("--height")
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(720.0);
+    let fullscreen = std::env::args().any(|arg| arg == "--fullscreen");
+    Window {
+        title: title.to_string(),
+        resolution: (width, height).into(),
+        mode: if fullscreen {
+            WindowMode::BorderlessFullscreen
+        } else {
+            WindowMode::Windowed
+        },
+        ..default()
+    }
+}
+
+fn toggle_fullscreen(keyboard: Res<Input<KeyCode>>, mut windows: Query<&mut Window>) {
+    if !keyboard.just_pressed(KeyCode::F11) {
+        return;
+    }
+    for mut window in windows.iter_mut() {
+        window.mode = match window.mode {
+            WindowMode::Windowed => WindowMode::BorderlessFullscreen,
+            _ => WindowMode::Windowed,
+        };
+    }
+}
+
+// [F1] 0.25x, [F2] 1x, [F3] 4x: skala waktu global untuk semua sistem yang memakai Time
+fn time_scale_system(keyboard: Res<Input<KeyCode>>, mut time: ResMut<Time<Virtual>>) {
+    let scale = if keyboard.just_pressed(KeyCode::F1) {
+        0.25
+    } else if keyboard.just_pressed(KeyCode::F2) {
+        1.0
+    } else if keyboard.just_pressed(KeyCode::F3) {
+        4.0
+    } else {
+        return;
+    };
+    time.set_relative_speed(scale);
+    println!("⏱️ Time scale: {}x", scale);
+}
+
+// Kamera free-fly ([Tab]): WASD maju/mundur/geser, Q/E turun/naik, mouse untuk
+// melihat (kursor dikunci). Selama aktif tombol-tombol itu "ditelan" di PreUpdate
+// supaya tidak ikut memicu fitur lain; [Tab] lagi mengembalikan kamera semula.
+#[derive(Component)]
+struct FreeFlyCamera {
+    yaw: f32,
+    pitch: f32,
+    saved: Transform,
+}
+
+fn free_fly_camera_system(
+    mut commands: Commands,
+    mut keyboard: ResMut<Input<KeyCode>>,
+    mut mouse_motion: EventReader<MouseMotion>,
+    time: Res<Time>,
+    mut windows: Query<&mut Window>,
+    mut cameras: Query<(Entity, &mut Transform, Option<&mut FreeFlyCamera>), With<Camera3d>>,
+) {
+    let Ok((entity, mut transform, free_fly)) = cameras.get_single_mut() else {
+        return;
+    };
+
+    if keyboard.just_pressed(KeyCode::Tab) {
+        let grab = free_fly.is_none();
+        if let Some(free_fly) = free_fly {
+            *transform = free_fly.saved;
+            commands.entity(entity).remove::<FreeFlyCamera>();
+        } else {
+            let (yaw, pitch, _) = transform.rotation.to_euler(EulerRot::YXZ);
+            commands.entity(entity).insert(FreeFlyCamera {
+                yaw,
+                pitch,
+                saved: *transform,
+            });
+        }
+        for mut window in windows.iter_mut() {
+            window.cursor.grab_mode = if grab {
+                CursorGrabMode::Locked
+            } else {
+                CursorGrabMode::None
+            };
+            window.cursor.visible = !grab;
+        }
+        return;
+    }
+    let Some(mut free_fly) = free_fly else {
+        return;
+    };
+
+    let look: Vec2 = mouse_motion.read().map(|motion| motion.delta).sum();
+    free_fly.yaw -= look.x * FREE_FLY_SENSITIVITY;
+    free_fly.pitch = (free_fly.pitch - look.y * FREE_FLY_SENSITIVITY).clamp(-1.54, 1.54);
+    transform.rotation = Quat::from_euler(EulerRot::YXZ, free_fly.yaw, free_fly.pitch, 0.0);
+
+    let mut direction = Vec3::ZERO;
+    for (key, axis) in [
+        (KeyCode::W, transform.forward()),
+        (KeyCode::S, -transform.forward()),
+        (KeyCode::D, transform.right()),
+        (KeyCode::A, -transform.right()),
+        (KeyCode::E, Vec3::Y),
+        (KeyCode::Q, -Vec3::Y),
+    ] {
+        if keyboard.pressed(key) {
+            direction += axis;
+        }
+        keyboard.reset(key);
+    }
+    transform.translation += direction.normalize_or_zero() * FREE_FLY_SPEED * time.delta_seconds();
+}
+
+// [V]: kamera perspektif 3D <-> orthographic top-down; posisi kamera 3D
+// disimpan supaya bisa kembali ke sudut pandang sebelumnya.
+fn toggle_projection(
+    keyboard: Res<Input<KeyCode>>,
+    mut cameras: Query<(&mut Projection, &mut Transform), With<Camera3d>>,
+    mut saved: Local<Option<Transform>>,
+) {
+    if !keyboard.just_pressed(KeyCode::V) {
+        return;
+    }
+    for (mut projection, mut transform) in cameras.iter_mut() {
+        if let Projection::Perspective(_) = *projection {
+            *saved = Some(*transform);
+            *projection = Projection::Orthographic(OrthographicProjection {
+                scaling_mode: ScalingMode::FixedVertical(ARENA_HALF * 2.0 + 2.0),
+                ..default()
+            });
+            *transform = Transform::from_xyz(0.0, 30.0, 0.0).looking_at(Vec3::ZERO, Vec3::NEG_Z);
+        } else {
+            *projection = Projection::Perspective(default());
+            if let Some(saved) = saved.take() {
+                *transform = saved;
+            }
+        }
+    }
+}
+
+fn print_summary_on_exit(mut exit_events: EventReader<AppExit>, time: Res<Time>) {
+    if exit_events.read().next().is_some() {
+        println!("\nRun duration: {:.1}s", time.elapsed_seconds());
+    }
+}
+
+// --- MODE COLLECT ---
+
+#[derive(Component)]
+struct Collectible;
+
+#[derive(Component)]
+struct ScoreText;
+
+#[derive(Resource, Default)]
+struct Score {
+    player: usize,
+    npcs: usize,
+    win_score: usize,
+    winner: Option<&'static str>,
+}
+
+fn setup_score_hud(mut commands: Commands) {
+    commands.spawn((
+        TextBundle::from_section(
+            "",
+            TextStyle {
+                font_size: 22.0,
+                color: Color::WHITE,
+                ..default()
+            },
         )
-        .run();
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            top: Val::Px(12.0),
+            left: Val::Px(12.0),
+            ..default()
+        }),
+        ScoreText,
+    ));
+}
+
+// Jaga supaya selalu ada COLLECTIBLE_COUNT collectible di posisi acak
+fn spawn_collectibles(
+    mut commands: Commands,
+    collectibles: Query<(), With<Collectible>>,
+    score: Res<Score>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    if score.winner.is_some() {
+        return;
+    }
+    let mut rng = rand::thread_rng();
+    for _ in collectibles.iter().count()..COLLECTIBLE_COUNT {
+        let x = rng.gen_range(-ARENA_HALF + 1.0..ARENA_HALF - 1.0);
+        let z = rng.gen_range(-ARENA_HALF + 1.0..ARENA_HALF - 1.0);
+        commands.spawn((
+            PbrBundle {
+                mesh: meshes.add(Mesh::from(shape::UVSphere {
+                    radius: 0.35,
+                    ..default()
+                })),
+                material: materials.add(StandardMaterial {
+                    base_color: Color::GOLD,
+                    emissive: Color::GOLD,
+                    ..default()
+                }),
+                transform: Transform::from_xyz(x, 0.5, z),
+                ..default()
+            },
+            Collectible,
+        ));
+    }
+}
+
+// Seeker (selain state machine) mengejar collectible terdekat, bukan pemain
+fn collectible_target_system(
+    mut seekers: Query<(&Transform, &mut Seek), Without<StateMachine>>,
+    collectibles: Query<(Entity, &Transform), With<Collectible>>,
+) {
+    for (transform, mut seek) in seekers.iter_mut() {
+        let nearest = collectibles.iter().min_by(|a, b| {
+            let da = a.1.translation.distance_squared(transform.translation);
+            let db = b.1.translation.distance_squared(transform.translation);
+            da.total_cmp(&db)
+        });
+        if let Some((entity, _)) = nearest {
+            seek.target = entity;
+        }
+    }
+}
+
+fn collect_system(
+    mut commands: Commands,
+    mut score: ResMut<Score>,
+    collectibles: Query<(Entity, &Transform), With<Collectible>>,
+    player: Query<&Transform, With<Player>>,
+    seekers: Query<&Transform, (With<Seek>, Without<StateMachine>)>,
+) {
+    if score.winner.is_some() {
+        return;
+    }
+    let touches = |a: &Transform, b: &Transform| {
+        a.translation.xz().distance(b.translation.xz()) < COLLECT_RADIUS
+    };
+
+    for (entity, collectible) in collectibles.iter() {
+        if player.iter().any(|p| touches(p, collectible)) {
+            score.player += 1;
+        } else if seekers.iter().any(|s| touches(s, collectible)) {
+            score.npcs += 1;
+        } else {
+            continue;
+        }
+        commands.entity(entity).despawn();
+    }
+
+    if score.player >= score.win_score {
+        score.winner = Some("Player");
+    } else if score.npcs >= score.win_score {
+        score.winner = Some("Seekers");
+    }
+    if let Some(winner) = score.winner {
+        println!(
+            "{} win! Player {} - Seekers {}",
+            winner, score.player, score.npcs
+        );
+    }
+}
+
+fn score_text_system(score: Res<Score>, mut text_query: Query<&mut Text, With<ScoreText>>) {
+    if !score.is_changed() {
+        return;
+    }
+    for mut text in text_query.iter_mut() {
+        text.sections[0].value = match score.winner {
+            Some(winner) => format!(
+                "{} win!  Player {} - Seekers {}",
+                winner, score.player, score.npcs
+            ),
+            None => format!(
+                "Player {} - Seekers {}  (first to {})",
+                score.player, score.npcs, score.win_score
+            ),
+        };
+    }
+}
+
+// Garis grid di atas lantai sebagai patokan jarak & kecepatan
+#[derive(Resource)]
+struct FloorGrid(bool);
+
+fn floor_grid_system(
+    keyboard: Res<Input<KeyCode>>,
+    mut floor_grid: ResMut<FloorGrid>,
+    mut gizmos: Gizmos,
+) {
+    if keyboard.just_pressed(KeyCode::G) {
+        floor_grid.0 = !floor_grid.0;
+    }
+    if !floor_grid.0 {
+        return;
+    }
+
+    let lines = (ARENA_HALF * 2.0 / FLOOR_GRID_SPACING).round() as usize;
+    let color = Color::rgba(1.0, 1.0, 1.0, 0.25);
+    for i in 0..=lines {
+        let offset = -ARENA_HALF + i as f32 * FLOOR_GRID_SPACING;
+        // Sedikit di atas lantai supaya tidak z-fighting
+        gizmos.line(
+            Vec3::new(offset, 0.01, -ARENA_HALF),
+            Vec3::new(offset, 0.01, ARENA_HALF),
+            color,
+        );
+        gizmos.line(
+            Vec3::new(-ARENA_HALF, 0.01, offset),
+            Vec3::new(ARENA_HALF, 0.01, offset),
+            color,
+        );
+    }
 }
 
 // --- COMPONENTS ---
@@ -44,6 +444,25 @@ struct Agent {
     max_force: f32,
 }
 
+// Low-pass filter untuk total gaya kemudi per frame: factor 0 = langsung,
+// mendekati 1 = belokan makin halus karena gaya frame lalu lebih dominan.
+#[derive(Component)]
+struct SteeringSmoothing {
+    factor: f32,
+    last_force: Vec3,       // Gaya yang benar-benar diterapkan frame lalu
+    applied_velocity: Vec3, // Velocity setelah movement_system frame lalu
+}
+
+impl SteeringSmoothing {
+    fn new(factor: f32) -> Self {
+        Self {
+            factor,
+            last_force: Vec3::ZERO,
+            applied_velocity: Vec3::ZERO,
+        }
+    }
+}
+
 // Kecepatan saat ini dari sebuah entitas
 #[derive(Component, Default, Deref, DerefMut)]
 struct Velocity(Vec3);
@@ -61,9 +480,14 @@ struct Seek {
     target: Entity,
 }
 
+// Panik: di dalam panic_radius max_speed naik sampai (1 + panic_boost) kali,
+// turun linear ke normal di tepi radius. panic_boost 0 = tanpa panik.
 #[derive(Component)]
 struct Flee {
     target: Entity,
+    panic_radius: f32,
+    panic_boost: f32,
+    speed_multiplier: f32, // Dihitung flee_system, dipakai movement_system
 }
 
 #[derive(Component)]
@@ -80,14 +504,125 @@ struct Wander {
     angle_change: f32,
 }
 
+// Pursuit/Evade hanya bereaksi kalau target ada dalam vision_range;
+// di luar itu `in_sight` false dan Wander (kalau ada) yang mengambil alih.
 #[derive(Component)]
 struct Pursuit {
     target: Entity,
+    vision_range: f32,
+    in_sight: bool,
 }
 
 #[derive(Component)]
 struct Evade {
     target: Entity,
+    vision_range: f32,
+    in_sight: bool,
+}
+
+// Bias eksplorasi untuk wander: catat kapan tiap sel grid kasar terakhir
+// dikunjungi, lalu dorong agen ke sel yang paling lama tidak dikunjungi.
+#[derive(Component)]
+struct ExploreBias {
+    last_visit: [[f32; EXPLORE_GRID]; EXPLORE_GRID],
+    weight: f32,
+}
+
+// Menghindari rintangan dengan "melihat ke depan" sejauh look_ahead.
+// Selama `avoiding` bernilai true, avoidance punya prioritas lebih tinggi
+// dari seek sehingga seek tidak bisa mengalahkan gaya menghindar.
+#[derive(Component)]
+struct ObstacleAvoidance {
+    look_ahead: f32,
+    avoid_force: f32,
+    avoiding: bool,
+}
+
+// Mengikuti leader dari belakang (arrive ke titik di belakang leader)
+#[derive(Component)]
+struct FollowLeader {
+    leader: Entity,
+    behind_distance: f32,
+    slowing_radius: f32,
+}
+
+// Menjaga posisi slot formasi relatif terhadap target (offset dalam frame
+// lokal target: +X kanan, +Z belakang), didekati dengan arrive
+#[derive(Component)]
+struct FormationSlot {
+    target: Entity,
+    offset: Vec3,
+    slowing_radius: f32,
+}
+
+// Penanda bola kecil yang melayang di atas leader saat ini
+#[derive(Component)]
+struct LeaderIndicator;
+
+// Indeks leader di antara kandidat (pemain + NPC yang bukan follower)
+#[derive(Resource, Default)]
+struct LeaderIndex(usize);
+
+// NPC yang perilakunya dipilih state machine berdasarkan jarak ke target:
+// jauh -> wander, sedang -> seek, terlalu dekat -> flee.
+#[derive(Component, Clone, Copy, Debug, PartialEq)]
+enum BehaviorState {
+    Wander,
+    Seek,
+    Flee,
+}
+
+impl BehaviorState {
+    fn color(&self) -> Color {
+        match self {
+            BehaviorState::Wander => Color::LIME_GREEN,
+            BehaviorState::Seek => Color::CRIMSON,
+            BehaviorState::Flee => Color::AZURE,
+        }
+    }
+}
+
+#[derive(Component)]
+struct StateMachine {
+    target: Entity,
+    seek_radius: f32,
+    flee_radius: f32,
+}
+
+// Penjaga: patroli loop waypoint, kejar pemain yang masuk kerucut pandang,
+// lalu kembali ke waypoint terdekat setelah pemain lolos (di luar lose_range).
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum GuardState {
+    Patrol,
+    Chase,
+    Return,
+}
+
+impl GuardState {
+    fn color(&self) -> Color {
+        match self {
+            GuardState::Patrol => Color::GRAY,
+            GuardState::Chase => Color::MAROON,
+            GuardState::Return => Color::GOLD,
+        }
+    }
+}
+
+#[derive(Component)]
+struct Guard {
+    target: Entity,
+    waypoints: Vec<Vec3>,
+    current: usize, // Waypoint yang sedang dituju
+    vision_range: f32,
+    vision_cos: f32, // cos setengah sudut kerucut pandang
+    lose_range: f32, // Lebih besar dari vision_range supaya tidak bolak-balik di tepi
+    state: GuardState,
+}
+
+// Rintangan berbentuk silinder di lantai
+#[derive(Component)]
+struct Obstacle {
+    radius: f32,
 }
 
 // --- SETUP SYSTEM ---
@@ -116,6 +651,96 @@ fn setup(
         ))
         .id();
 
+    match stress_grid_size() {
+        Some(n) => spawn_stress_grid(&mut commands, &mut meshes, &mut materials, n),
+        None => spawn_demo_npcs(&mut commands, &mut meshes, &mut materials, player_entity),
+    }
+
+    // Rintangan
+    for (x, z, radius) in [
+        (-5.0, 0.0, 1.2),
+        (-2.0, -4.0, 1.0),
+        (3.0, 3.0, 1.5),
+        (-3.0, 6.0, 1.0),
+        (6.0, -5.0, 1.2),
+    ] {
+        commands.spawn((
+            PbrBundle {
+                mesh: meshes.add(Mesh::from(shape::Cylinder {
+                    radius,
+                    height: 1.5,
+                    ..default()
+                })),
+                material: materials.add(Color::rgb(0.4, 0.3, 0.2).into()),
+                transform: Transform::from_xyz(x, 0.75, z),
+                ..default()
+            },
+            Obstacle { radius },
+        ));
+    }
+
+    // Lantai
+    commands.spawn(PbrBundle {
+        mesh: meshes.add(shape::Plane::from_size(25.0).into()),
+        material: materials.add(Color::rgb(0.3, 0.5, 0.3).into()),
+        ..default()
+    });
+
+    // Cahaya
+    commands.spawn(PointLightBundle {
+        point_light: PointLight {
+            intensity: 1500.0,
+            shadows_enabled: true,
+            ..default()
+        },
+        transform: Transform::from_xyz(4.0, 8.0, 4.0),
+        ..default()
+    });
+
+    // Legenda warna NPC demo (scene --grid hanya berisi agen wander)
+    if stress_grid_size().is_none() {
+        spawn_legend(
+            &mut commands,
+            "Behaviors",
+            &[
+                (Color::rgb(0.2, 0.5, 0.9), "Player"),
+                (Color::RED, "Seek"),
+                (Color::YELLOW, "Flee (panics up close)"),
+                (Color::GREEN, "Arrive"),
+                (Color::PURPLE, "Wander (explores)"),
+                (Color::ORANGE, "Pursuit (else wander)"),
+                (Color::CYAN, "Evade (else wander)"),
+                (Color::PINK, "Seek + obstacle avoidance"),
+                (Color::WHITE, "Follow leader [L]"),
+                (Color::TEAL, "Formation (V behind player)"),
+                (BehaviorState::Wander.color(), "State machine: wander"),
+                (BehaviorState::Seek.color(), "State machine: seek"),
+                (BehaviorState::Flee.color(), "State machine: flee"),
+                (GuardState::Patrol.color(), "Guard: patrol"),
+                (GuardState::Chase.color(), "Guard: chase (player in view)"),
+                (GuardState::Return.color(), "Guard: return to route"),
+            ],
+            Style {
+                top: Val::Px(12.0),
+                right: Val::Px(12.0),
+                ..default()
+            },
+        );
+    }
+
+    // Kamera
+    commands.spawn(Camera3dBundle {
+        transform: Transform::from_xyz(-20.0, 25.0, 15.0).looking_at(Vec3::ZERO, Vec3::Y),
+        ..default()
+    });
+}
+
+fn spawn_demo_npcs(
+    commands: &mut Commands,
+    meshes: &mut Assets<Mesh>,
+    materials: &mut Assets<StandardMaterial>,
+    player_entity: Entity,
+) {
     // --- Spawn NPCs dengan Perilaku Berbeda ---
 
     // 1. SEEK (Merah) - Akan selalu bergerak lurus ke arah pemain.
@@ -151,6 +776,9 @@ fn setup(
         Velocity::default(),
         Flee {
             target: player_entity,
+            panic_radius: 4.0,
+            panic_boost: 1.0,
+            speed_multiplier: 1.0,
         },
     ));
 
@@ -173,7 +801,8 @@ fn setup(
         },
     ));
 
-    // 4. WANDER (Ungu) - Akan bergerak tanpa tujuan secara acak.
+    // 4. WANDER (Ungu) - Bergerak acak, dengan bias ke area arena yang
+    //    paling lama tidak dikunjungi supaya seluruh arena terjelajahi.
     commands.spawn((
         PbrBundle {
             mesh: meshes.add(Mesh::from(shape::Cube { size: 1.0 })),
@@ -192,6 +821,10 @@ fn setup(
             wander_angle: 0.0,
             angle_change: 0.4,
         },
+        ExploreBias {
+            last_visit: [[0.0; EXPLORE_GRID]; EXPLORE_GRID],
+            weight: 0.5,
+        },
     ));
 
     // 5. PURSUIT (Oranye) - Akan memprediksi posisi pemain dan mengejarnya.
@@ -209,7 +842,11 @@ fn setup(
         Velocity::default(),
         Pursuit {
             target: player_entity,
+            vision_range: 8.0,
+            in_sight: false,
         },
+        wander_behavior(),
+        SteeringSmoothing::new(0.7),
     ));
 
     // 6. EVADE (Cyan) - Akan memprediksi posisi pemain dan menghindarinya.
@@ -227,32 +864,249 @@ fn setup(
         Velocity::default(),
         Evade {
             target: player_entity,
+            vision_range: 8.0,
+            in_sight: false,
         },
+        wander_behavior(),
     ));
 
-    // Lantai
-    commands.spawn(PbrBundle {
-        mesh: meshes.add(shape::Plane::from_size(25.0).into()),
-        material: materials.add(Color::rgb(0.3, 0.5, 0.3).into()),
-        ..default()
-    });
+    // 7. SEEK + OBSTACLE AVOIDANCE (Pink) - Mengejar pemain sambil
+    //    meliuk menghindari rintangan di jalurnya.
+    commands.spawn((
+        PbrBundle {
+            mesh: meshes.add(Mesh::from(shape::Cube { size: 1.0 })),
+            material: materials.add(Color::PINK.into()),
+            transform: Transform::from_xyz(-10.0, 0.5, 0.0),
+            ..default()
+        },
+        Agent {
+            max_speed: 3.5,
+            max_force: 0.8,
+        },
+        Velocity::default(),
+        Seek {
+            target: player_entity,
+        },
+        ObstacleAvoidance {
+            look_ahead: 4.0,
+            avoid_force: 1.2,
+            avoiding: false,
+        },
+    ));
 
-    // Cahaya
-    commands.spawn(PointLightBundle {
-        point_light: PointLight {
-            intensity: 1500.0,
-            shadows_enabled: true,
+    // 8. FOLLOW LEADER (Putih) - Tiga follower yang berbaris di belakang leader.
+    //    Tekan [L] untuk mengganti leader.
+    for (i, x) in [8.0, 9.5, 11.0].into_iter().enumerate() {
+        commands.spawn((
+            PbrBundle {
+                mesh: meshes.add(Mesh::from(shape::Cube { size: 0.8 })),
+                material: materials.add(Color::WHITE.into()),
+                transform: Transform::from_xyz(x, 0.5, -8.0),
+                ..default()
+            },
+            Agent {
+                max_speed: 3.8,
+                max_force: 0.9,
+            },
+            Velocity::default(),
+            FollowLeader {
+                leader: player_entity,
+                behind_distance: 2.0 + i as f32 * 1.5,
+                slowing_radius: 3.0,
+            },
+        ));
+    }
+
+    // 9. STATE MACHINE (warna berubah sesuai state) - Mulai dengan wander,
+    //    komponen Seek/Flee/Wander dipasang-lepas oleh behavior_state_system.
+    commands.spawn((
+        PbrBundle {
+            mesh: meshes.add(Mesh::from(shape::Cube { size: 1.2 })),
+            material: materials.add(BehaviorState::Wander.color().into()),
+            transform: Transform::from_xyz(-8.0, 0.5, -4.0),
             ..default()
         },
-        transform: Transform::from_xyz(4.0, 8.0, 4.0),
-        ..default()
-    });
+        Agent {
+            max_speed: 3.2,
+            max_force: 0.8,
+        },
+        Velocity::default(),
+        BehaviorState::Wander,
+        StateMachine {
+            target: player_entity,
+            seek_radius: 10.0,
+            flee_radius: 3.5,
+        },
+        wander_behavior(),
+        SteeringSmoothing::new(0.7),
+    ));
 
-    // Kamera
-    commands.spawn(Camera3dBundle {
-        transform: Transform::from_xyz(-20.0, 25.0, 15.0).looking_at(Vec3::ZERO, Vec3::Y),
+    // 10. FORMATION (Teal) - Formasi V di belakang pemain, ikut berputar
+    //     sesuai arah hadap pemain.
+    for offset in [
+        Vec3::new(-1.5, 0.0, 1.5),
+        Vec3::new(1.5, 0.0, 1.5),
+        Vec3::new(-3.0, 0.0, 3.0),
+        Vec3::new(3.0, 0.0, 3.0),
+    ] {
+        commands.spawn((
+            PbrBundle {
+                mesh: meshes.add(Mesh::from(shape::Cube { size: 0.7 })),
+                material: materials.add(Color::TEAL.into()),
+                transform: Transform::from_xyz(offset.x, 0.5, offset.z + 4.0),
+                ..default()
+            },
+            Agent {
+                max_speed: 5.5,
+                max_force: 1.2,
+            },
+            Velocity::default(),
+            FormationSlot {
+                target: player_entity,
+                offset,
+                slowing_radius: 2.0,
+            },
+        ));
+    }
+
+    // 11. GUARD (abu-abu) - Patroli persegi di pojok, kejar pemain yang terlihat
+    //     di depannya (kerucut 90°), kembali ke rute kalau pemain lolos.
+    let waypoints = vec![
+        Vec3::new(5.0, 0.5, 6.0),
+        Vec3::new(10.0, 0.5, 6.0),
+        Vec3::new(10.0, 0.5, 10.0),
+        Vec3::new(5.0, 0.5, 10.0),
+    ];
+    commands.spawn((
+        PbrBundle {
+            mesh: meshes.add(Mesh::from(shape::Cube { size: 1.0 })),
+            material: materials.add(GuardState::Patrol.color().into()),
+            transform: Transform::from_translation(waypoints[0]),
+            ..default()
+        },
+        Agent {
+            max_speed: 3.5,
+            max_force: 0.6,
+        },
+        Velocity::default(),
+        Guard {
+            target: player_entity,
+            waypoints,
+            current: 1,
+            vision_range: 7.0,
+            vision_cos: 45f32.to_radians().cos(),
+            lose_range: 10.0,
+            state: GuardState::Patrol,
+        },
+    ));
+
+    commands.spawn((
+        PbrBundle {
+            mesh: meshes.add(Mesh::from(shape::UVSphere {
+                radius: 0.25,
+                ..default()
+            })),
+            material: materials.add(StandardMaterial {
+                base_color: Color::WHITE,
+                emissive: Color::WHITE,
+                ..default()
+            }),
+            transform: Transform::from_xyz(0.0, 2.5, 0.0),
+            ..default()
+        },
+        LeaderIndicator,
+    ));
+}
+
+// --grid N: ganti NPC demo dengan N x N agen wander untuk uji performa.
+// Mesh & material dibagi supaya yang diukur hanya biaya sistem steering.
+fn stress_grid_size() -> Option<usize> {
+    arg_value("--grid").and_then(|v| v.parse().ok())
+}
+
+fn spawn_stress_grid(
+    commands: &mut Commands,
+    meshes: &mut Assets<Mesh>,
+    materials: &mut Assets<StandardMaterial>,
+    n: usize,
+) {
+    let mesh = meshes.add(Mesh::from(shape::Cube { size: 0.6 }));
+    let material = materials.add(Color::PURPLE.into());
+    let spacing = (ARENA_HALF - 1.0) * 2.0 / n.max(1) as f32;
+    let mut rng = rand::thread_rng();
+
+    for i in 0..n {
+        for j in 0..n {
+            let x = -ARENA_HALF + 1.0 + (i as f32 + 0.5) * spacing;
+            let z = -ARENA_HALF + 1.0 + (j as f32 + 0.5) * spacing;
+            commands.spawn((
+                PbrBundle {
+                    mesh: mesh.clone(),
+                    material: material.clone(),
+                    transform: Transform::from_xyz(x, 0.5, z),
+                    ..default()
+                },
+                Agent {
+                    max_speed: 2.5,
+                    max_force: 0.5,
+                },
+                Velocity::default(),
+                Wander {
+                    wander_angle: rng.gen_range(0.0..std::f32::consts::TAU),
+                    ..wander_behavior()
+                },
+            ));
+        }
+    }
+    println!("Stress scene: {} agents", n * n);
+}
+
+// Legenda warna: kotak warna + keterangan per baris. `position` hanya
+// mengisi top/left/right/bottom, sisanya diatur di sini.
+fn spawn_legend(commands: &mut Commands, title: &str, entries: &[(Color, &str)], position: Style) {
+    let text_style = |font_size| TextStyle {
+        font_size,
+        color: Color::WHITE,
         ..default()
-    });
+    };
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                flex_direction: FlexDirection::Column,
+                padding: UiRect::all(Val::Px(8.0)),
+                row_gap: Val::Px(4.0),
+                ..position
+            },
+            background_color: Color::rgba(0.0, 0.0, 0.0, 0.6).into(),
+            ..default()
+        })
+        .with_children(|parent| {
+            parent.spawn(TextBundle::from_section(title, text_style(15.0)));
+            for &(color, label) in entries {
+                parent
+                    .spawn(NodeBundle {
+                        style: Style {
+                            align_items: AlignItems::Center,
+                            column_gap: Val::Px(6.0),
+                            ..default()
+                        },
+                        ..default()
+                    })
+                    .with_children(|row| {
+                        row.spawn(NodeBundle {
+                            style: Style {
+                                width: Val::Px(14.0),
+                                height: Val::Px(14.0),
+                                ..default()
+                            },
+                            background_color: color.into(),
+                            ..default()
+                        });
+                        row.spawn(TextBundle::from_section(label, text_style(14.0)));
+                    });
+            }
+        });
 }
 
 // --- BEHAVIOR SYSTEMS ---
@@ -260,10 +1114,21 @@ fn setup(
 
 // 1. SEEK SYSTEM
 fn seek_system(
-    mut agent_query: Query<(&mut Velocity, &Transform, &Agent, &Seek)>,
+    mut agent_query: Query<(
+        &mut Velocity,
+        &Transform,
+        &Agent,
+        &Seek,
+        Option<&ObstacleAvoidance>,
+    )>,
     target_query: Query<&Transform>,
 ) {
-    for (mut velocity, transform, agent, seek) in agent_query.iter_mut() {
+    for (mut velocity, transform, agent, seek, avoidance) in agent_query.iter_mut() {
+        // Arbitrase prioritas: saat sedang menghindar, seek dilewati
+        if avoidance.is_some_and(|a| a.avoiding) {
+            continue;
+        }
+
         if let Ok(target_transform) = target_query.get(seek.target) {
             let desired = target_transform.translation - transform.translation;
             let desired_velocity = desired.normalize_or_zero() * agent.max_speed;
@@ -275,13 +1140,20 @@ fn seek_system(
 
 // 2. FLEE SYSTEM
 fn flee_system(
-    mut agent_query: Query<(&mut Velocity, &Transform, &Agent, &Flee)>,
+    mut agent_query: Query<(&mut Velocity, &Transform, &Agent, &mut Flee)>,
     target_query: Query<&Transform>,
 ) {
-    for (mut velocity, transform, agent, flee) in agent_query.iter_mut() {
+    for (mut velocity, transform, agent, mut flee) in agent_query.iter_mut() {
         if let Ok(target_transform) = target_query.get(flee.target) {
             let desired = transform.translation - target_transform.translation;
-            let desired_velocity = desired.normalize_or_zero() * agent.max_speed;
+            let closeness = if flee.panic_radius > 0.0 {
+                (1.0 - desired.length() / flee.panic_radius).clamp(0.0, 1.0)
+            } else {
+                0.0
+            };
+            flee.speed_multiplier = 1.0 + flee.panic_boost * closeness;
+            let desired_velocity =
+                desired.normalize_or_zero() * agent.max_speed * flee.speed_multiplier;
             let steering = (desired_velocity - velocity.0).clamp_length_max(agent.max_force);
             velocity.0 += steering;
         }
@@ -309,9 +1181,22 @@ fn arrive_system(
 }
 
 // 4. WANDER SYSTEM
-fn wander_system(mut query: Query<(&mut Velocity, &Transform, &Agent, &mut Wander)>) {
+type WanderItem<'a> = (
+    &'a mut Velocity,
+    &'a Agent,
+    &'a mut Wander,
+    Option<&'a Pursuit>,
+    Option<&'a Evade>,
+);
+
+fn wander_system(mut query: Query<WanderItem>) {
     let mut rng = rand::thread_rng();
-    for (mut velocity, _transform, agent, mut wander) in query.iter_mut() {
+    for (mut velocity, agent, mut wander, pursuit, evade) in query.iter_mut() {
+        // Wander hanya fallback: lewati kalau pursuit/evade sedang melihat target
+        if pursuit.is_some_and(|p| p.in_sight) || evade.is_some_and(|e| e.in_sight) {
+            continue;
+        }
+
         let circle_center = velocity.normalize_or_zero() * wander.circle_distance;
 
         let displacement = Vec3::new(wander.wander_angle.cos(), 0.0, wander.wander_angle.sin())
@@ -324,14 +1209,60 @@ fn wander_system(mut query: Query<(&mut Velocity, &Transform, &Agent, &mut Wande
     }
 }
 
+// 4b. EXPLORE BIAS SYSTEM
+// Dijalankan setelah wander; gaya ke sel yang paling lama tidak dikunjungi
+// dicampur dengan gaya wander acak sesuai `weight`.
+fn explore_bias_system(
+    mut query: Query<(&mut Velocity, &Transform, &Agent, &mut ExploreBias)>,
+    time: Res<Time>,
+) {
+    let cell_size = ARENA_HALF * 2.0 / EXPLORE_GRID as f32;
+    let to_cell = |v: f32| (((v + ARENA_HALF) / cell_size) as usize).min(EXPLORE_GRID - 1);
+    let now = time.elapsed_seconds();
+
+    for (mut velocity, transform, agent, mut bias) in query.iter_mut() {
+        let (cx, cz) = (
+            to_cell(transform.translation.x),
+            to_cell(transform.translation.z),
+        );
+        bias.last_visit[cz][cx] = now;
+
+        // Sel tertua; kalau sama tua, pilih yang terdekat
+        let mut best: Option<(f32, f32, Vec3)> = None;
+        for (z, row) in bias.last_visit.iter().enumerate() {
+            for (x, &visited) in row.iter().enumerate() {
+                let center = Vec3::new(
+                    -ARENA_HALF + (x as f32 + 0.5) * cell_size,
+                    transform.translation.y,
+                    -ARENA_HALF + (z as f32 + 0.5) * cell_size,
+                );
+                let distance = center.distance(transform.translation);
+                if best.is_none_or(|(t, d, _)| visited < t || (visited == t && distance < d)) {
+                    best = Some((visited, distance, center));
+                }
+            }
+        }
+
+        if let Some((_, _, center)) = best {
+            let desired = (center - transform.translation).normalize_or_zero() * agent.max_speed;
+            let steering = (desired - velocity.0).clamp_length_max(agent.max_force);
+            velocity.0 += steering * bias.weight;
+        }
+    }
+}
+
 // 5. PURSUIT SYSTEM
 fn pursuit_system(
-    mut agent_query: Query<(&mut Velocity, &Transform, &Agent, &Pursuit), Without<Player>>,
+    mut agent_query: Query<(&mut Velocity, &Transform, &Agent, &mut Pursuit), Without<Player>>,
     target_query: Query<(&Transform, &Velocity), With<Player>>,
 ) {
-    for (mut velocity, transform, agent, pursuit) in agent_query.iter_mut() {
+    for (mut velocity, transform, agent, mut pursuit) in agent_query.iter_mut() {
         if let Ok((target_transform, target_velocity)) = target_query.get(pursuit.target) {
             let distance = (target_transform.translation - transform.translation).length();
+            pursuit.in_sight = distance <= pursuit.vision_range;
+            if !pursuit.in_sight {
+                continue;
+            }
             let prediction_time = distance / agent.max_speed;
             let future_position =
                 target_transform.translation + target_velocity.0 * prediction_time;
@@ -346,12 +1277,16 @@ fn pursuit_system(
 
 // 6. EVADE SYSTEM
 fn evade_system(
-    mut agent_query: Query<(&mut Velocity, &Transform, &Agent, &Evade), Without<Player>>,
+    mut agent_query: Query<(&mut Velocity, &Transform, &Agent, &mut Evade), Without<Player>>,
     target_query: Query<(&Transform, &Velocity), With<Player>>,
 ) {
-    for (mut velocity, transform, agent, evade) in agent_query.iter_mut() {
+    for (mut velocity, transform, agent, mut evade) in agent_query.iter_mut() {
         if let Ok((target_transform, target_velocity)) = target_query.get(evade.target) {
             let distance = (target_transform.translation - transform.translation).length();
+            evade.in_sight = distance <= evade.vision_range;
+            if !evade.in_sight {
+                continue;
+            }
             let prediction_time = distance / agent.max_speed;
             let future_position =
                 target_transform.translation + target_velocity.0 * prediction_time;
@@ -364,6 +1299,243 @@ fn evade_system(
     }
 }
 
+// 7. OBSTACLE AVOIDANCE SYSTEM
+// Cari rintangan terdekat yang memotong garis pandang ke depan agen,
+// lalu dorong agen ke samping menjauhi pusat rintangan tersebut.
+fn obstacle_avoidance_system(
+    mut agent_query: Query<(&mut Velocity, &Transform, &mut ObstacleAvoidance)>,
+    obstacle_query: Query<(&Transform, &Obstacle)>,
+) {
+    for (mut velocity, transform, mut avoidance) in agent_query.iter_mut() {
+        avoidance.avoiding = false;
+
+        let heading = Vec3::new(velocity.x, 0.0, velocity.z).normalize_or_zero();
+        if heading == Vec3::ZERO {
+            continue;
+        }
+
+        let position = Vec3::new(transform.translation.x, 0.0, transform.translation.z);
+        let mut most_threatening: Option<(f32, Vec3)> = None;
+
+        for (obstacle_transform, obstacle) in obstacle_query.iter() {
+            let center = Vec3::new(
+                obstacle_transform.translation.x,
+                0.0,
+                obstacle_transform.translation.z,
+            );
+
+            // Titik terdekat pada garis pandang ke pusat rintangan
+            let along = (center - position)
+                .dot(heading)
+                .clamp(0.0, avoidance.look_ahead);
+            let closest = position + heading * along;
+            let lateral = closest - center;
+
+            if lateral.length() < obstacle.radius + AGENT_RADIUS
+                && most_threatening.is_none_or(|(best, _)| along < best)
+            {
+                // Kalau tepat di tengah, belok ke kanan dari arah gerak
+                let away = if lateral.length_squared() > 0.0001 {
+                    lateral.normalize()
+                } else {
+                    Vec3::new(-heading.z, 0.0, heading.x)
+                };
+                most_threatening = Some((along, away));
+            }
+        }
+
+        if let Some((_, away)) = most_threatening {
+            avoidance.avoiding = true;
+            velocity.0 += away * avoidance.avoid_force;
+        }
+    }
+}
+
+// 8. FOLLOW LEADER SYSTEM
+// Follower melakukan arrive ke titik di belakang leader (berdasarkan arah hadap leader).
+fn follow_leader_system(
+    mut agent_query: Query<(&mut Velocity, &Transform, &Agent, &FollowLeader)>,
+    leader_query: Query<&Transform, Without<FollowLeader>>,
+) {
+    for (mut velocity, transform, agent, follow) in agent_query.iter_mut() {
+        if let Ok(leader_transform) = leader_query.get(follow.leader) {
+            let behind =
+                leader_transform.translation - leader_transform.forward() * follow.behind_distance;
+            let desired = Vec3::new(
+                behind.x - transform.translation.x,
+                0.0,
+                behind.z - transform.translation.z,
+            );
+            let distance = desired.length();
+            let speed = agent.max_speed * (distance / follow.slowing_radius).min(1.0);
+            let desired_velocity = desired.normalize_or_zero() * speed;
+            let steering = (desired_velocity - velocity.0).clamp_length_max(agent.max_force);
+            velocity.0 += steering;
+        }
+    }
+}
+
+fn wander_behavior() -> Wander {
+    Wander {
+        circle_distance: 3.0,
+        circle_radius: 1.5,
+        wander_angle: 0.0,
+        angle_change: 0.4,
+    }
+}
+
+// 9. BEHAVIOR STATE SYSTEM
+// Pilih state dari jarak ke target, lalu tukar komponen perilaku supaya
+// sistem seek/flee/wander yang sudah ada yang menjalankannya.
+fn behavior_state_system(
+    mut commands: Commands,
+    mut agent_query: Query<(
+        Entity,
+        &Transform,
+        &StateMachine,
+        &mut BehaviorState,
+        &Handle<StandardMaterial>,
+    )>,
+    target_query: Query<&Transform, Without<StateMachine>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    for (entity, transform, machine, mut state, material) in agent_query.iter_mut() {
+        let Ok(target_transform) = target_query.get(machine.target) else {
+            continue;
+        };
+        let distance = transform.translation.distance(target_transform.translation);
+
+        let next = if distance < machine.flee_radius {
+            BehaviorState::Flee
+        } else if distance < machine.seek_radius {
+            BehaviorState::Seek
+        } else {
+            BehaviorState::Wander
+        };
+        if next == *state {
+            continue;
+        }
+
+        let mut npc = commands.entity(entity);
+        npc.remove::<(Seek, Flee, Wander)>();
+        match next {
+            BehaviorState::Wander => npc.insert(wander_behavior()),
+            BehaviorState::Seek => npc.insert(Seek {
+                target: machine.target,
+            }),
+            BehaviorState::Flee => npc.insert(Flee {
+                target: machine.target,
+                panic_radius: machine.flee_radius,
+                panic_boost: 0.5,
+                speed_multiplier: 1.0,
+            }),
+        };
+        if let Some(material) = materials.get_mut(material) {
+            material.base_color = next.color();
+        }
+        *state = next;
+    }
+}
+
+// 10. FORMATION SYSTEM
+fn formation_system(
+    mut agent_query: Query<(&mut Velocity, &Transform, &Agent, &FormationSlot)>,
+    target_query: Query<&Transform, Without<FormationSlot>>,
+) {
+    for (mut velocity, transform, agent, slot) in agent_query.iter_mut() {
+        if let Ok(target_transform) = target_query.get(slot.target) {
+            let slot_pos = target_transform.translation + target_transform.rotation * slot.offset;
+            let desired = Vec3::new(
+                slot_pos.x - transform.translation.x,
+                0.0,
+                slot_pos.z - transform.translation.z,
+            );
+            let distance = desired.length();
+            let speed = agent.max_speed * (distance / slot.slowing_radius).min(1.0);
+            let desired_velocity = desired.normalize_or_zero() * speed;
+            let steering = (desired_velocity - velocity.0).clamp_length_max(agent.max_force);
+            velocity.0 += steering;
+        }
+    }
+}
+
+// 11. GUARD SYSTEM
+// Patrol -> Chase saat pemain dalam jarak pandang & di depan penjaga,
+// Chase -> Return saat pemain lebih jauh dari lose_range, Return -> Patrol
+// setelah sampai lagi di waypoint terdekat.
+type GuardItem<'a> = (
+    &'a mut Velocity,
+    &'a Transform,
+    &'a Agent,
+    &'a mut Guard,
+    &'a Handle<StandardMaterial>,
+);
+
+fn guard_system(
+    mut guard_query: Query<GuardItem, Without<Player>>,
+    target_query: Query<(&Transform, &Velocity), With<Player>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    for (mut velocity, transform, agent, mut guard, material) in guard_query.iter_mut() {
+        let Ok((target_transform, target_velocity)) = target_query.get(guard.target) else {
+            continue;
+        };
+        let to_target = target_transform.translation - transform.translation;
+        let distance = to_target.length();
+        // movement_system menghadapkan agen ke arah gerak
+        let sees_target = distance <= guard.vision_range
+            && transform.forward().dot(to_target.normalize_or_zero()) >= guard.vision_cos;
+
+        let next = match guard.state {
+            GuardState::Patrol | GuardState::Return if sees_target => GuardState::Chase,
+            GuardState::Chase if distance > guard.lose_range => {
+                let position = transform.translation;
+                guard.current = (0..guard.waypoints.len())
+                    .min_by(|&a, &b| {
+                        let da = guard.waypoints[a].distance(position);
+                        let db = guard.waypoints[b].distance(position);
+                        da.total_cmp(&db)
+                    })
+                    .unwrap_or(0);
+                GuardState::Return
+            }
+            state => state,
+        };
+        if next != guard.state {
+            if let Some(material) = materials.get_mut(material) {
+                material.base_color = next.color();
+            }
+            guard.state = next;
+        }
+
+        let destination = match guard.state {
+            GuardState::Chase => {
+                // Seperti pursuit: tuju posisi pemain yang diprediksi
+                let prediction_time = distance / agent.max_speed;
+                target_transform.translation + target_velocity.0 * prediction_time
+            }
+            GuardState::Patrol | GuardState::Return => {
+                let waypoint = guard.waypoints[guard.current];
+                if transform.translation.distance(waypoint) < GUARD_WAYPOINT_RADIUS {
+                    guard.current = (guard.current + 1) % guard.waypoints.len();
+                    if guard.state == GuardState::Return {
+                        guard.state = GuardState::Patrol;
+                        if let Some(material) = materials.get_mut(material) {
+                            material.base_color = GuardState::Patrol.color();
+                        }
+                    }
+                }
+                guard.waypoints[guard.current]
+            }
+        };
+
+        let desired = destination - transform.translation;
+        let desired_velocity = desired.normalize_or_zero() * agent.max_speed;
+        let steering = (desired_velocity - velocity.0).clamp_length_max(agent.max_force);
+        velocity.0 += steering;
+    }
+}
+
 // --- COMBINATION SYSTEMS ---
 
 // SEPARATION SYSTEM
@@ -418,10 +1590,31 @@ fn containment_system(mut query: Query<(&mut Velocity, &Transform, &Agent)>) {
 // MOVEMENT SYSTEM
 // Sistem ini menerapkan Velocity akhir ke Transform (posisi) dan
 // memutar agen agar menghadap ke arah gerakannya.
-fn movement_system(mut query: Query<(&mut Transform, &mut Velocity, &Agent)>, time: Res<Time>) {
-    for (mut transform, mut velocity, agent) in query.iter_mut() {
-        // Batasi kecepatan maksimum
-        velocity.0 = velocity.0.clamp_length_max(agent.max_speed);
+type MovementItem<'a> = (
+    &'a mut Transform,
+    &'a mut Velocity,
+    &'a Agent,
+    Option<&'a mut SteeringSmoothing>,
+    Option<&'a Flee>,
+);
+
+fn movement_system(mut query: Query<MovementItem>, time: Res<Time>) {
+    for (mut transform, mut velocity, agent, mut smoothing, flee) in query.iter_mut() {
+        // Semua behavior menambah ke Velocity, jadi selisihnya terhadap
+        // velocity frame lalu = total gaya kemudi frame ini
+        if let Some(smoothing) = smoothing.as_mut() {
+            let force = velocity.0 - smoothing.applied_velocity;
+            let blended = smoothing.last_force.lerp(force, 1.0 - smoothing.factor);
+            velocity.0 = smoothing.applied_velocity + blended;
+            smoothing.last_force = blended;
+        }
+
+        // Batasi kecepatan maksimum (boleh lebih saat flee sedang panik)
+        let max_speed = agent.max_speed * flee.map_or(1.0, |flee| flee.speed_multiplier);
+        velocity.0 = velocity.0.clamp_length_max(max_speed);
+        if let Some(smoothing) = smoothing.as_mut() {
+            smoothing.applied_velocity = velocity.0;
+        }
 
         // Perbarui posisi
         transform.translation += velocity.0 * time.delta_seconds();
@@ -437,6 +1630,92 @@ fn movement_system(mut query: Query<(&mut Transform, &mut Velocity, &Agent)>, ti
     }
 }
 
+// Agen makin terang sesuai |velocity| / max_speed: pengejar yang ngebut
+// menyala, agen yang sudah tiba meredup. Warna dasar tidak diubah.
+#[derive(Resource)]
+struct SpeedGlow(bool);
+
+fn speed_glow_system(
+    keyboard: Res<Input<KeyCode>>,
+    mut speed_glow: ResMut<SpeedGlow>,
+    query: Query<(&Velocity, &Agent, &Handle<StandardMaterial>)>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    let toggled = keyboard.just_pressed(KeyCode::T);
+    if toggled {
+        speed_glow.0 = !speed_glow.0;
+    }
+    if !speed_glow.0 && !toggled {
+        return;
+    }
+
+    for (velocity, agent, handle) in query.iter() {
+        let Some(material) = materials.get_mut(handle) else {
+            continue;
+        };
+        // Dimatikan: kembalikan emissive ke hitam sekali saat toggle
+        let ratio = if speed_glow.0 {
+            (velocity.length() / agent.max_speed).min(1.0)
+        } else {
+            0.0
+        };
+        material.emissive = material.base_color * (ratio * SPEED_GLOW);
+    }
+}
+
+// LEADER SYSTEMS
+// Kandidat leader: pemain lebih dulu, lalu NPC lain (bukan follower) urut berdasarkan Entity.
+fn leader_candidates(
+    player_query: &Query<Entity, With<Player>>,
+    npc_query: &Query<Entity, (With<Agent>, Without<FollowLeader>)>,
+) -> Vec<Entity> {
+    let mut npcs: Vec<Entity> = npc_query.iter().collect();
+    npcs.sort();
+    player_query.iter().chain(npcs).collect()
+}
+
+// Tekan [L] untuk memindahkan leader ke kandidat berikutnya;
+// semua follower langsung menarget leader yang baru.
+fn cycle_leader_system(
+    keyboard_input: Res<Input<KeyCode>>,
+    mut leader_index: ResMut<LeaderIndex>,
+    player_query: Query<Entity, With<Player>>,
+    npc_query: Query<Entity, (With<Agent>, Without<FollowLeader>)>,
+    mut follower_query: Query<&mut FollowLeader>,
+) {
+    if !keyboard_input.just_pressed(KeyCode::L) {
+        return;
+    }
+
+    let candidates = leader_candidates(&player_query, &npc_query);
+    if candidates.is_empty() {
+        return;
+    }
+
+    leader_index.0 = (leader_index.0 + 1) % candidates.len();
+    let new_leader = candidates[leader_index.0];
+    for mut follow in follower_query.iter_mut() {
+        follow.leader = new_leader;
+    }
+    println!("Leader -> kandidat #{} ({:?})", leader_index.0, new_leader);
+}
+
+// Posisikan penanda di atas leader yang sedang aktif
+fn leader_indicator_system(
+    follower_query: Query<&FollowLeader>,
+    target_query: Query<&Transform, Without<LeaderIndicator>>,
+    mut indicator_query: Query<&mut Transform, With<LeaderIndicator>>,
+) {
+    let Some(follow) = follower_query.iter().next() else {
+        return;
+    };
+    if let Ok(leader_transform) = target_query.get(follow.leader) {
+        for mut indicator in indicator_query.iter_mut() {
+            indicator.translation = leader_transform.translation + Vec3::Y * 1.5;
+        }
+    }
+}
+
 // PLAYER MOVEMENT SYSTEM
 // Mengizinkan Anda mengontrol pemain dengan tombol panah/WASD.
 fn player_movement_system(